@@ -0,0 +1,98 @@
+//! Paints two `LyonCallback`s that share one renderer into an offscreen egui
+//! frame and prints the pixel at the center of each.
+//!
+//! Run with `cargo run --example egui --features egui,headless`.
+
+use egui_wgpu::{Callback, Renderer, ScreenDescriptor};
+use epaint::{ClippedPrimitive, Primitive, Rect, pos2};
+use lyon_tessellation::FillOptions;
+use lyon_tessellation::math::point;
+use wgpu_lyon::{LyonRenderer, Shape};
+use wgpu_lyon::egui::LyonCallback;
+
+const SIZE: u32 = 64;
+
+/// A quad covering the whole callback rect.
+fn quad(color: [f32; 3]) -> Shape {
+    Shape::new(move |builder| {
+        let attributes = [color[0], color[1], color[2], 0.5];
+        builder.begin(point(-1.0, -1.0), &attributes);
+        builder.line_to(point(1.0, -1.0), &attributes);
+        builder.line_to(point(1.0, 1.0), &attributes);
+        builder.line_to(point(-1.0, 1.0), &attributes);
+        builder.end(true);
+    }, (0, 0, SIZE, SIZE))
+}
+
+fn callback(rect: Rect, color: [f32; 3]) -> ClippedPrimitive {
+    let callback = LyonCallback::new(vec![quad(color)], FillOptions::default(), rect);
+    ClippedPrimitive{clip_rect: rect, primitive: Primitive::Callback(Callback::new_paint_callback(rect, callback))}
+}
+
+fn main() {
+    let (lyon_renderer, device, queue) = pollster::block_on(LyonRenderer::headless_async())
+        .expect("No device for headless rendering");
+    let mut egui_renderer = Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm, None, 1, false);
+    egui_renderer.callback_resources.insert(lyon_renderer);
+
+    let half = SIZE as f32 / 2.0;
+    let paint_jobs = [
+        callback(Rect::from_min_max(pos2(0.0, 0.0), pos2(half, SIZE as f32)), [1.0, 0.0, 0.0]),
+        callback(Rect::from_min_max(pos2(half, 0.0), pos2(SIZE as f32, SIZE as f32)), [0.0, 0.0, 1.0]),
+    ];
+    let screen_descriptor = ScreenDescriptor{size_in_pixels: [SIZE, SIZE], pixels_per_point: 1.0};
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d{width: SIZE, height: SIZE, depth_or_array_layers: 1},
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    let callback_buffers = egui_renderer.update_buffers(&device, &queue, &mut encoder, &paint_jobs, &screen_descriptor);
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations{load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store},
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        }).forget_lifetime();
+        egui_renderer.render(&mut render_pass, &paint_jobs, &screen_descriptor);
+    }
+
+    let bytes_per_row = (SIZE * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (bytes_per_row * SIZE) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    encoder.copy_texture_to_buffer(
+        target.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout{offset: 0, bytes_per_row: Some(bytes_per_row), rows_per_image: None},
+        },
+        target.size()
+    );
+    queue.submit(callback_buffers.into_iter().chain(std::iter::once(encoder.finish())));
+    readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+
+    let pixels = readback.slice(..).get_mapped_range();
+    let row = (SIZE / 2 * bytes_per_row) as usize;
+    for x in [SIZE / 4, SIZE * 3 / 4] {
+        let offset = row + x as usize * 4;
+        println!("pixel ({}, {}): {:?}", x, SIZE / 2, &pixels[offset..offset + 4]);
+    }
+}
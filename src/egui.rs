@@ -0,0 +1,134 @@
+use egui_wgpu::{CallbackResources, ScreenDescriptor, CallbackTrait};
+use epaint::{PaintCallbackInfo, Rect};
+use wgpu::{CommandEncoder, CommandBuffer, RenderPass, Device, Queue};
+use lyon_tessellation::FillOptions;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{LyonRenderer, Shape, TessellatedFrame};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The shapes of every callback in an egui frame, kept in the callback
+/// resources next to the renderer. Each callback tessellates into `pending`
+/// and the first `finish_prepare` uploads them all, so the renderer's buffers
+/// hold the shapes of every callback when they paint.
+#[derive(Default)]
+struct CallbackFrames {
+    pending: Option<TessellatedFrame>,
+    ranges: HashMap<u64, Range<usize>>
+}
+
+/// Draws shapes from a `LyonRenderer` stored in egui's callback resources, e.g.
+/// with `egui_renderer.callback_resources.insert(lyon_renderer)`. The renderer
+/// must be built for egui's target format and sample count.
+///
+/// egui sets the viewport to the callback rect, so geometry is in the clip space
+/// of that rect. Shape bounds are in physical pixels and are clipped to the
+/// egui clip rect, which is scaled by the pixels per point. Several callbacks
+/// can share the renderer in one frame, each painting only its own shapes.
+pub struct LyonCallback {
+    id: u64,
+    shapes: Mutex<Vec<Shape>>,
+    fill_options: FillOptions,
+    clip_rect: Rect
+}
+
+impl LyonCallback {
+    /// `clip_rect` is in points, usually `ui.clip_rect()`.
+    pub fn new(shapes: Vec<Shape>, fill_options: FillOptions, clip_rect: Rect) -> Self {
+        LyonCallback{
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            shapes: Mutex::new(shapes),
+            fill_options,
+            clip_rect
+        }
+    }
+}
+
+impl CallbackTrait for LyonCallback {
+    fn prepare(
+        &self,
+        _device: &Device,
+        _queue: &Queue,
+        screen_descriptor: &ScreenDescriptor,
+        _egui_encoder: &mut CommandEncoder,
+        callback_resources: &mut CallbackResources,
+    ) -> Vec<CommandBuffer> {
+        let Some(renderer) = callback_resources.get_mut::<LyonRenderer>() else {
+            log::warn!("No LyonRenderer in the egui callback resources");
+            return Vec::new();
+        };
+
+        let ppp = screen_descriptor.pixels_per_point;
+        let [width, height] = screen_descriptor.size_in_pixels;
+        let left = ((self.clip_rect.min.x * ppp).round().max(0.0) as u32).min(width);
+        let top = ((self.clip_rect.min.y * ppp).round().max(0.0) as u32).min(height);
+        let right = ((self.clip_rect.max.x * ppp).round().max(0.0) as u32).min(width);
+        let bottom = ((self.clip_rect.max.y * ppp).round().max(0.0) as u32).min(height);
+
+        let shapes = std::mem::take(&mut *self.shapes.lock().unwrap());
+        renderer.push_clip((left, top, right.saturating_sub(left), bottom.saturating_sub(top)));
+        let result = renderer.tessellate(&self.fill_options, shapes);
+        renderer.pop_clip();
+        let frame = match result {
+            Ok(frame) => frame,
+            Err(error) => {
+                log::error!("Failed to prepare shapes for egui: {}", error);
+                return Vec::new();
+            }
+        };
+
+        let frames = callback_resources.entry::<CallbackFrames>().or_insert_with(CallbackFrames::default);
+        //The first callback of a frame finds the last frame's shapes uploaded
+        let pending = match frames.pending.take() {
+            Some(pending) => pending,
+            None => {
+                frames.ranges.clear();
+                TessellatedFrame::default()
+            }
+        };
+        let start = pending.draws.len();
+        match pending.merge(frame) {
+            Ok(pending) => {
+                frames.ranges.insert(self.id, start..pending.draws.len());
+                frames.pending = Some(pending);
+            }
+            Err(error) => log::error!("Failed to prepare shapes for egui: {}", error)
+        }
+        Vec::new()
+    }
+
+    fn finish_prepare(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        _egui_encoder: &mut CommandEncoder,
+        callback_resources: &mut CallbackResources,
+    ) -> Vec<CommandBuffer> {
+        let Some(frame) = callback_resources.get_mut::<CallbackFrames>().and_then(|frames| frames.pending.take()) else {
+            return Vec::new();
+        };
+        if let Some(renderer) = callback_resources.get_mut::<LyonRenderer>() {
+            if let Err(error) = renderer.upload(device, queue, frame) {
+                log::error!("Failed to upload shapes for egui: {}", error);
+            }
+        }
+        Vec::new()
+    }
+
+    fn paint(
+        &self,
+        _info: PaintCallbackInfo,
+        render_pass: &mut RenderPass<'static>,
+        callback_resources: &CallbackResources,
+    ) {
+        let range = callback_resources.get::<CallbackFrames>().and_then(|frames| frames.ranges.get(&self.id));
+        if let (Some(renderer), Some(range)) = (callback_resources.get::<LyonRenderer>(), range) {
+            renderer.render_shapes(render_pass, range.clone());
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, COPY_BUFFER_ALIGNMENT, VertexBufferLayout, DepthStencilState, MultisampleState, BufferDescriptor, RenderPipeline, PrimitiveState, VertexStepMode, FragmentState, TextureFormat, BufferAddress, BufferUsages, IndexFormat, VertexState, RenderPass, Buffer, Device, Queue};
 
 use lyon_tessellation::{
     FillVertexConstructor,
@@ -6,15 +5,587 @@ use lyon_tessellation::{
     FillOptions,
     FillBuilder,
     FillVertex,
+    TessellationError,
+    GeometryBuilderError,
+    GeometryBuilder,
+    FillGeometryBuilder,
     BuffersBuilder,
     VertexBuffers,
+    VertexId,
+    FillRule,
+    StrokeOptions,
+    StrokeTessellator,
+    StrokeVertex,
+    StrokeVertexConstructor,
+    StrokeGeometryBuilder,
+    LineCap,
 };
+use lyon_tessellation::path::{builder::PathBuilder, iterator::PathIterator, Winding, Path, PathEvent};
+use lyon_tessellation::math::{Box2D, Point, Vector, Transform};
+use lyon_tessellation::geom;
+
+#[cfg(feature = "debug-attributes")]
+use lyon_tessellation::VertexSource;
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+#[cfg(feature = "wgpu")]
+mod renderer;
+#[cfg(feature = "wgpu")]
+pub use renderer::{LyonRenderer, LyonRendererBuilder, PrepareScratch, PassRequirements, RenderGroup, Grid, Outline, DebugMode, Lighting, ColorInterpolation, Shading, depth_only, depth_stencil_clip, depth_stencil_inverse_clip, depth_stencil_mask};
+
+#[cfg(feature = "egui")]
+pub mod egui;
+
+#[cfg(feature = "testing")]
+pub mod testing;
 
 type Bound = (u32, u32, u32, u32);
 
+/// Conversion of a `(x, y, width, height)` pixel bound to viewport arguments.
+pub trait AsViewport {
+    /// The `(x, y, width, height, min_depth, max_depth)` arguments of
+    /// `set_viewport` covering the bound, with the default depth range of 0 to 1.
+    fn as_viewport(&self) -> (f32, f32, f32, f32, f32, f32);
+}
+
+impl AsViewport for Bound {
+    fn as_viewport(&self) -> (f32, f32, f32, f32, f32, f32) {
+        (self.0 as f32, self.1 as f32, self.2 as f32, self.3 as f32, 0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LyonError {
+    /// The texture format does not support the renderer's multisample count.
+    #[cfg(feature = "wgpu")]
+    UnsupportedSampleCount{format: wgpu::TextureFormat, count: u32},
+    /// A shape produced geometry but its bound has no width or height, so its
+    /// scissor would clip everything.
+    EmptyBound{shape: usize, bound: Bound},
+    /// Like `EmptyBound`, for a normalized bound without a positive width and
+    /// height.
+    EmptyNormalizedBound{shape: usize, bound: [f32; 4]},
+    /// Growing a vertex or index buffer would exceed the builder's `max_buffer_bytes`.
+    BufferTooLarge{size: u64, max: u64},
+    /// The total index count passed `u32::MAX` while tessellating the shape.
+    TooManyIndices{shape: usize},
+    /// Triangles pushed with `TessellatedFrame::push_triangles` had a vertex
+    /// count that is not a multiple of three.
+    IncompleteTriangle{shape: usize, vertices: usize},
+    /// A frame for `VertexLayout::Uniform` has more shapes than fit in the
+    /// shape color uniform.
+    TooManyShapes{count: usize, max: usize},
+    /// The bind group index is used by the crate's own uniforms.
+    ReservedBindGroup{index: u32},
+    /// The pipelines could not be created with the caller's pipeline layout,
+    /// usually because it does not match the shader's bindings.
+    #[cfg(feature = "wgpu")]
+    PipelineLayout{message: String},
+    /// The pipelines could not be created from a custom shader, e.g. because
+    /// it does not compile or lacks an entry point.
+    #[cfg(feature = "wgpu")]
+    Shader{message: String},
+    /// The builder's options need device features the device was not created with.
+    #[cfg(feature = "wgpu")]
+    MissingFeature{features: wgpu::Features},
+    /// No adapter was found for `LyonRenderer::headless_async`.
+    #[cfg(feature = "headless")]
+    NoAdapter,
+    /// The adapter refused the device requested by `LyonRenderer::headless_async`.
+    #[cfg(feature = "headless")]
+    RequestDevice{message: String},
+    /// A fill builder made outside the crate, checked with `check_attributes`,
+    /// has a different number of attributes than the vertex layout. Shape
+    /// constructors passing slices of the wrong length are not caught, as
+    /// lyon's builder stores them without checking in release builds.
+    AttributeCountMismatch{expected: usize, got: usize},
+    /// A builder's attribute count, checked with
+    /// `VertexConstructor::check_attributes`, matches no `VertexLayout`.
+    UnknownAttributeCount{got: usize},
+    Tessellation{shape: usize, error: TessellationError},
+}
+
+impl std::fmt::Display for LyonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "wgpu")]
+            LyonError::UnsupportedSampleCount{format, count} =>
+                write!(f, "Texture format {:?} does not support a sample count of {}", format, count),
+            LyonError::EmptyBound{shape, bound} =>
+                write!(f, "Shape {} has geometry but an empty bound {:?}", shape, bound),
+            LyonError::EmptyNormalizedBound{shape, bound} =>
+                write!(f, "Shape {} has geometry but an empty normalized bound {:?}", shape, bound),
+            LyonError::BufferTooLarge{size, max} =>
+                write!(f, "Buffer of {} bytes exceeds the maximum of {} bytes", size, max),
+            LyonError::TooManyIndices{shape} =>
+                write!(f, "Shape {} pushed the index count past u32::MAX", shape),
+            LyonError::IncompleteTriangle{shape, vertices} =>
+                write!(f, "Shape {} has {} vertices, which is not three per triangle", shape, vertices),
+            LyonError::TooManyShapes{count, max} =>
+                write!(f, "Frame has {} shapes but at most {} can take their color from a uniform", count, max),
+            LyonError::ReservedBindGroup{index} =>
+                write!(f, "Bind group {} is reserved by the renderer", index),
+            #[cfg(feature = "wgpu")]
+            LyonError::PipelineLayout{message} =>
+                write!(f, "Pipeline layout is incompatible with the shader: {}", message),
+            #[cfg(feature = "wgpu")]
+            LyonError::Shader{message} => write!(f, "Failed to build the custom shader: {}", message),
+            #[cfg(feature = "wgpu")]
+            LyonError::MissingFeature{features} => write!(f, "The device lacks the required {:?}", features),
+            #[cfg(feature = "headless")]
+            LyonError::NoAdapter => write!(f, "No graphics adapter is available"),
+            #[cfg(feature = "headless")]
+            LyonError::RequestDevice{message} => write!(f, "Failed to request a device: {}", message),
+            LyonError::AttributeCountMismatch{expected, got} =>
+                write!(f, "Expected {} builder attributes for the vertex layout but got {}", expected, got),
+            LyonError::UnknownAttributeCount{got} => {
+                let counts: Vec<String> = VertexLayout::ALL.iter().map(|layout| layout.attribute_count().to_string()).collect();
+                write!(f, "Expected one of {} builder attributes for a vertex layout but got {}", counts.join(", "), got)
+            }
+            LyonError::Tessellation{shape, error} =>
+                write!(f, "Failed to tessellate shape {}: {}", shape, error),
+        }
+    }
+}
+
+impl std::error::Error for LyonError {}
+
+impl LyonError {
+    /// Shift the shape index of errors from a chunk of shapes starting at `offset`.
+    fn offset_shape(self, offset: usize) -> Self {
+        match self {
+            LyonError::EmptyBound{shape, bound} => LyonError::EmptyBound{shape: shape + offset, bound},
+            LyonError::EmptyNormalizedBound{shape, bound} => LyonError::EmptyNormalizedBound{shape: shape + offset, bound},
+            LyonError::TooManyIndices{shape} => LyonError::TooManyIndices{shape: shape + offset},
+            LyonError::Tessellation{shape, error} => LyonError::Tessellation{shape: shape + offset, error},
+            error => error
+        }
+    }
+}
+
 pub struct Shape {
-    pub constructor: Box<dyn Fn(&mut FillBuilder)>,
-    pub bound: Bound
+    pub constructor: Box<dyn Fn(&mut FillBuilder) + Send + Sync>,
+    pub bound: Bound,
+    /// Drawn behind the shape as part of the same draw.
+    pub shadow: Option<Shadow>,
+    /// `[x, y, width, height]` as fractions of the render target, used
+    /// instead of `bound` and converted to pixels when rendering.
+    pub normalized_bound: Option<[f32; 4]>,
+    /// Set on every vertex of the shape and its shadow in place of the z
+    /// attribute, e.g. for `VertexLayout::Color` shapes, which have none.
+    pub z: Option<f32>,
+    /// More bounds the shape is drawn in, clipping it to their union with
+    /// `bound`. Not used with a normalized bound.
+    pub union_bounds: Vec<Bound>,
+    /// Written as the output alpha, as `id / 255`, by renderers built with
+    /// `material_ids`.
+    pub material_id: Option<u8>,
+    /// Whether edges are left to multisampling. See `with_antialias`.
+    pub antialias: bool,
+    /// Only draw the shape inside this shape's fill. See `with_clip_mask`.
+    pub clip_mask: Option<Box<Shape>>,
+    pub style: ShapeStyle,
+    /// The units of the stroke width of `style` and `stroke_path`. See
+    /// `with_stroke_width_space`.
+    pub stroke_width_space: StrokeWidthSpace,
+    /// Stroked as built, open contours included, in place of filling the
+    /// constructor's path. Its attributes follow the layout like the
+    /// constructor's. See `polyline`.
+    pub stroke_path: Option<(Path, StrokeOptions)>,
+    /// Drawn through this matrix in place of the camera. See `with_transform`.
+    pub transform: Option<[[f32; 4]; 4]>
+}
+
+impl Shape {
+    pub fn new(constructor: impl Fn(&mut FillBuilder) + Send + Sync + 'static, bound: Bound) -> Self {
+        Shape{constructor: Box::new(constructor), bound, shadow: None, normalized_bound: None, z: None, union_bounds: Vec::new(), material_id: None, antialias: true,
+            clip_mask: None, style: ShapeStyle::Fill, stroke_width_space: StrokeWidthSpace::World, stroke_path: None, transform: None}
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    /// Clip to the union of `bound` and `bounds`, e.g. several visible slots.
+    /// The shape is drawn once per bound, but overlaps are split off first so
+    /// no pixel is drawn twice, even under additive blending.
+    pub fn with_union_bounds(mut self, bounds: Vec<Bound>) -> Self {
+        self.union_bounds = bounds;
+        self
+    }
+
+    pub fn with_z(mut self, z: f32) -> Self {
+        self.z = Some(z);
+        self
+    }
+
+    /// Tag the shape for a G-buffer, e.g. a material id read back from the
+    /// alpha channel of an `Rgba8Unorm` target. The id replaces the alpha of
+    /// every vertex, the shadow's included, so the shape cannot be translucent.
+    /// See `LyonRendererBuilder::material_ids`.
+    pub fn with_material_id(mut self, material_id: u8) -> Self {
+        self.material_id = Some(material_id);
+        self
+    }
+
+    /// With `false`, `LyonRenderer::prepare` snaps every vertex, the shadow's
+    /// included, to the nearest pixel corner as seen through the first camera,
+    /// so axis aligned edges cover whole pixels and stay hard under
+    /// multisampling, e.g. for pixel aligned UI rects. Edges at an angle are
+    /// still smoothed by multisampling. Snapping takes the camera and target
+    /// size at `prepare`, so prepare again after either changes. On by default.
+    pub fn with_antialias(mut self, antialias: bool) -> Self {
+        self.antialias = antialias;
+        self
+    }
+
+    /// Only draw the shape where `mask` is filled, e.g. a photo clipped to a
+    /// leaf silhouette. The mask is tessellated with the even-odd rule, so the
+    /// overlaps of a self-intersecting mask are left out, and positioned like
+    /// the shape but without its z, shadow or clips. Only its constructor and
+    /// `antialias` are used, and its attributes must follow the same layout.
+    ///
+    /// Masks go through the stencil, see `LyonRendererBuilder::clip_masks`.
+    pub fn with_clip_mask(mut self, mask: Shape) -> Self {
+        self.clip_mask = Some(Box::new(mask));
+        self
+    }
+
+    /// Fill the shape, stroke its outline or both, e.g. to toggle an outline
+    /// on hover without defining the shape twice.
+    pub fn with_style(mut self, style: ShapeStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Measure the stroke width of `style` and `stroke_path` in shape units or
+    /// in target pixels.
+    /// See `StrokeWidthSpace`.
+    pub fn with_stroke_width_space(mut self, space: StrokeWidthSpace) -> Self {
+        self.stroke_width_space = space;
+        self
+    }
+
+    /// Draw the shape through a column major `transform` instead of the camera,
+    /// e.g. to move many shapes each frame without tessellating them again.
+    /// `LyonRenderer::prepare` packs the transforms of every shape into a
+    /// uniform buffer the renderer reuses across frames, growing it for frames
+    /// with more. Bounds, clips and pixel snapping are unaffected.
+    ///
+    /// Ignored by `render_with_transforms`, `render_stereo` and renderers made
+    /// with `new_with_layout`.
+    pub fn with_transform(mut self, transform: [[f32; 4]; 4]) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Clip to a rect relative to the target size, so the clip follows resizes
+    /// without tessellating again. The renderer needs the size from `resize`,
+    /// and does not draw the shape until it has one. `prepare` returns
+    /// `EmptyNormalizedBound` for a rect without a positive width and height,
+    /// while one narrower than half a pixel of the target draws nothing.
+    pub fn with_normalized_bound(mut self, bound: [f32; 4]) -> Self {
+        self.normalized_bound = Some(bound);
+        self
+    }
+}
+
+/// How `prepare` turns a shape into triangles.
+///
+/// Strokes follow the outline of the shape's fill, holes included, so they
+/// are closed even where the path was left open (see `Shape::polyline` for
+/// open strokes), and take their color and z from the fill's vertices at
+/// each corner. Stroke widths are in shape units, before the coordinate
+/// transform, unless the shape's `stroke_width_space` is `Screen`. Stroked
+/// vertices have no texture coordinates or normals from the builder.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ShapeStyle {
+    #[default]
+    Fill,
+    Stroke(StrokeOptions),
+    /// The fill with the stroke drawn over it, in the shape's single draw.
+    Both(StrokeOptions)
+}
+
+impl ShapeStyle {
+    fn with_line_width_scale(self, scale: f32) -> Self {
+        if scale == 1.0 || !scale.is_finite() {return self;}
+        match self {
+            ShapeStyle::Fill => ShapeStyle::Fill,
+            ShapeStyle::Stroke(options) => ShapeStyle::Stroke(options.with_line_width(options.line_width * scale)),
+            ShapeStyle::Both(options) => ShapeStyle::Both(options.with_line_width(options.line_width * scale))
+        }
+    }
+}
+
+/// The units of a shape's stroke width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrokeWidthSpace {
+    /// Shape units, so the stroke scales with the camera and the coordinate
+    /// transform like the fill.
+    #[default]
+    World,
+    /// Target pixels as seen through the coordinate transform and the first
+    /// camera when the renderer prepares the shape, e.g. for hairlines that
+    /// stay one pixel wide at any zoom. The width is taken from the camera and
+    /// target size at `prepare`, so prepare again after zooming, or enable
+    /// `auto_retessellate_on_scale`. Under a non-uniform scale the width is
+    /// divided by the mean scale, so it comes out wider along one axis than
+    /// the other. Shape units are used when the target size is unknown,
+    /// e.g. for `TessellatedFrame::new` or before `resize`.
+    Screen
+}
+
+/// A soft shadow made of an offset copy of the shape's geometry and a ring
+/// fading out over `blur_radius` around its outline.
+///
+/// The fade follows a smoothstep rather than a true gaussian, starting at the
+/// outline instead of centering on it. Where the ring overlaps itself, at
+/// concave corners and narrow parts, the shadow is darker.
+///
+/// The shadow is drawn and scissored with the shape, so its bound has to
+/// include the offset and blur.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub offset: Vector,
+    pub blur_radius: f32,
+    pub color: [f32; 3],
+    pub alpha: f32
+}
+
+/// Geometry passed to these helpers is in the same space as any other shape
+/// (clip space under the default camera), while `bound` stays in target pixels.
+impl Shape {
+    pub fn rect(rect: Box2D, color: [f32; 3], z: f32, bound: Bound) -> Self {
+        let attrs = [color[0], color[1], color[2], z];
+        Shape::new(move |builder: &mut FillBuilder| {
+            builder.add_rectangle(&rect, Winding::Positive, &attrs);
+        }, bound)
+    }
+
+    /// An open polyline through `points`, stroked `width` wide with the start
+    /// and end caps of `caps` and lyon's default miter joins, e.g. for a
+    /// freehand pen stroke. Two points give a single capped segment.
+    pub fn polyline(points: &[Point], width: f32, caps: (LineCap, LineCap), color: [f32; 3], z: f32, bound: Bound) -> Self {
+        let attrs = [color[0], color[1], color[2], z];
+        let mut path = Path::builder_with_attributes(Vertex::ATTRIBUTE_COUNT);
+        if let Some((first, rest)) = points.split_first() {
+            path.begin(*first, &attrs);
+            for point in rest {
+                path.line_to(*point, &attrs);
+            }
+            path.end(false);
+        }
+        let options = StrokeOptions::default().with_line_width(width).with_start_cap(caps.0).with_end_cap(caps.1);
+        let mut shape = Shape::new(|_| {}, bound);
+        shape.stroke_path = Some((path.build(), options));
+        shape
+    }
+
+    /// A filled arc, closed by the chord between its endpoints.
+    pub fn arc(arc: geom::Arc<f32>, color: [f32; 3], z: f32, bound: Bound) -> Self {
+        let attrs = [color[0], color[1], color[2], z];
+        Shape::new(move |builder: &mut FillBuilder| {
+            builder.begin(arc.from(), &attrs);
+            arc.for_each_quadratic_bezier(&mut |curve| {
+                builder.quadratic_bezier_to(curve.ctrl, curve.to, &attrs);
+            });
+            builder.end(true);
+        }, bound)
+    }
+
+    /// The integer bound of the filled geometry, or of the `stroke_path`,
+    /// without any stroke from `style`, taking positions as pixels. Curves are
+    /// flattened first, so control points outside the curve do not widen it.
+    /// Negative coordinates are clamped to zero.
+    pub fn compute_bounds(&self, fill_options: &FillOptions) -> Result<Bound, TessellationError> {
+        self.compute_bounds_with_layout(fill_options, VertexLayout::ColorZ)
+    }
+
+    /// Like `compute_bounds`, for a shape that provides the builder attributes
+    /// of `layout`.
+    pub fn compute_bounds_with_layout(&self, fill_options: &FillOptions, layout: VertexLayout) -> Result<Bound, TessellationError> {
+        let mut buffers: VertexBuffers<Vertex, u16> = VertexBuffers::new();
+        let mut buffer = BuffersBuilder::new(&mut buffers, VertexConstructor);
+        match &self.stroke_path {
+            Some((path, options)) => StrokeTessellator::new().tessellate_path(path, options, &mut buffer)?,
+            None => {
+                let mut tessellator = FillTessellator::new();
+                let mut builder = tessellator.builder_with_attributes(layout.attribute_count(), fill_options, &mut buffer);
+                (self.constructor)(&mut builder);
+                builder.build()?;
+            }
+        }
+
+        if buffers.indices.is_empty() {return Ok((0, 0, 0, 0));}
+        let bounds = Box2D::from_points(buffers.vertices.iter().map(|vertex| Point::from(vertex.position)));
+        let x = bounds.min.x.floor().max(0.0) as u32;
+        let y = bounds.min.y.floor().max(0.0) as u32;
+        let right = bounds.max.x.ceil().max(0.0) as u32;
+        let bottom = bounds.max.y.ceil().max(0.0) as u32;
+        Ok((x, y, right - x, bottom - y))
+    }
+}
+
+/// Contours wound opposite to the outline cut holes under both the even-odd
+/// and non-zero fill rules. Overlapping holes refill their overlap under either
+/// rule, so they should be merged into a single contour first.
+impl Shape {
+    /// A disc of `outer_radius` with a hole of `inner_radius`.
+    pub fn ring(center: Point, outer_radius: f32, inner_radius: f32, color: [f32; 3], z: f32, bound: Bound) -> Self {
+        let attrs = [color[0], color[1], color[2], z];
+        Shape::new(move |builder: &mut FillBuilder| {
+            builder.add_circle(center, outer_radius, Winding::Positive, &attrs);
+            builder.add_circle(center, inner_radius, Winding::Negative, &attrs);
+        }, bound)
+    }
+
+    /// A polygon with polygonal holes, each wound against the outline.
+    pub fn with_holes(outer: Vec<Point>, holes: Vec<Vec<Point>>, color: [f32; 3], z: f32, bound: Bound) -> Self {
+        let attrs = [color[0], color[1], color[2], z];
+        let outer_area = signed_area(&outer);
+        let holes: Vec<Vec<Point>> = holes.into_iter().map(|mut hole| {
+            if signed_area(&hole).signum() == outer_area.signum() {hole.reverse();}
+            hole
+        }).collect();
+        Shape::new(move |builder: &mut FillBuilder| {
+            for contour in std::iter::once(&outer).chain(&holes) {
+                builder.add_polygon(lyon_tessellation::path::Polygon{points: contour, closed: true}, &attrs);
+            }
+        }, bound)
+    }
+}
+
+/// A glyph outline positioned relative to its pen position.
+pub struct Glyph {
+    pub outline: Path,
+    pub advance: f32,
+    /// Overrides the color of the run for this glyph.
+    pub color: Option<[f32; 3]>
+}
+
+impl Shape {
+    /// Lay glyphs out left to right starting at `origin`, moving the pen by each
+    /// glyph's advance. Glyphs without an outline, like spaces, only advance.
+    ///
+    /// Glyphs keep their fractional positions rather than being snapped or
+    /// hinted to pixels, so small text relies on multisampling for its edges;
+    /// 4x is recommended. The builder's `position_quantization` snaps them.
+    pub fn text_run(glyphs: Vec<Glyph>, origin: Point, color: [f32; 3], z: f32, bound: Bound) -> Self {
+        Shape::new(move |builder: &mut FillBuilder| {
+            //Accumulate in f64 so long runs don't drift
+            let mut pen = origin.x as f64;
+            for glyph in &glyphs {
+                let color = glyph.color.unwrap_or(color);
+                let attrs = [color[0], color[1], color[2], z];
+                let offset = Vector::new(pen as f32, origin.y);
+                add_path(builder, glyph.outline.iter(), offset, &attrs);
+                pen += glyph.advance as f64;
+            }
+        }, bound)
+    }
+}
+
+impl Shape {
+    /// Like `text_run`, stretching each glyph outline about its center to at
+    /// least `min_size` wide and tall so tiny glyphs or thin strokes do not
+    /// fall between samples and vanish. The size is in the units of the
+    /// outlines and is measured over their control points.
+    pub fn text_run_min_size(
+        mut glyphs: Vec<Glyph>,
+        origin: Point,
+        color: [f32; 3],
+        z: f32,
+        min_size: f32,
+        bound: Bound
+    ) -> Self {
+        for glyph in &mut glyphs {
+            let points = glyph.outline.iter().flat_map(|event| match event {
+                PathEvent::Begin{at} => vec![at],
+                PathEvent::Line{to, ..} => vec![to],
+                PathEvent::Quadratic{ctrl, to, ..} => vec![ctrl, to],
+                PathEvent::Cubic{ctrl1, ctrl2, to, ..} => vec![ctrl1, ctrl2, to],
+                PathEvent::End{..} => vec![]
+            });
+            let size = Box2D::from_points(points);
+            let scale = |extent: f32| if extent > 0.0 && extent < min_size {min_size / extent} else {1.0};
+            let (x, y) = (scale(size.width()), scale(size.height()));
+            if x == 1.0 && y == 1.0 {continue;}
+            let center = size.center().to_vector();
+            let stretch = Transform::translation(-center.x, -center.y).then_scale(x, y).then_translate(center);
+            glyph.outline = std::mem::take(&mut glyph.outline).transformed(&stretch);
+        }
+        Shape::text_run(glyphs, origin, color, z, bound)
+    }
+}
+
+fn add_path(builder: &mut FillBuilder, events: impl Iterator<Item = PathEvent>, offset: Vector, attrs: &[f32]) {
+    for event in events {
+        match event {
+            PathEvent::Begin{at} => {builder.begin(at + offset, attrs);},
+            PathEvent::Line{to, ..} => {builder.line_to(to + offset, attrs);},
+            PathEvent::Quadratic{ctrl, to, ..} => {builder.quadratic_bezier_to(ctrl + offset, to + offset, attrs);},
+            PathEvent::Cubic{ctrl1, ctrl2, to, ..} => {
+                builder.cubic_bezier_to(ctrl1 + offset, ctrl2 + offset, to + offset, attrs);
+            },
+            PathEvent::End{close, ..} => builder.end(close),
+        }
+    }
+}
+
+impl Shape {
+    /// The smallest tolerance used by `flattened`, so tiny shapes are not split
+    /// into a huge number of segments.
+    pub const MIN_TOLERANCE: f32 = 0.0001;
+
+    /// A filled path flattened with a tolerance of `relative_tolerance` times its
+    /// bounding radius, so its curves keep roughly the same number of segments
+    /// at any size regardless of the `FillOptions` tolerance.
+    pub fn flattened(path: Path, relative_tolerance: f32, color: [f32; 3], z: f32, bound: Bound) -> Self {
+        let attrs = [color[0], color[1], color[2], z];
+        //Control points keep the radius conservative, which only lowers the tolerance
+        let mut points = Vec::new();
+        for event in path.iter() {
+            match event {
+                PathEvent::Begin{at} => points.push(at),
+                PathEvent::Line{to, ..} => points.push(to),
+                PathEvent::Quadratic{ctrl, to, ..} => points.extend([ctrl, to]),
+                PathEvent::Cubic{ctrl1, ctrl2, to, ..} => points.extend([ctrl1, ctrl2, to]),
+                PathEvent::End{..} => {},
+            }
+        }
+        let radius = Box2D::from_points(points).size().to_vector().length() / 2.0;
+        let tolerance = (relative_tolerance * radius).max(Self::MIN_TOLERANCE);
+        Shape::new(move |builder: &mut FillBuilder| {
+            add_path(builder, path.iter().flattened(tolerance), Vector::zero(), &attrs);
+        }, bound)
+    }
+}
+
+fn signed_area(points: &[Point]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+/// A white rectangle at z 0.
+impl From<(Box2D, Bound)> for Shape {
+    fn from((rect, bound): (Box2D, Bound)) -> Self {
+        Shape::rect(rect, [1.0, 1.0, 1.0], 0.0, bound)
+    }
+}
+
+/// A white arc at z 0.
+impl From<(geom::Arc<f32>, Bound)> for Shape {
+    fn from((arc, bound): (geom::Arc<f32>, Bound)) -> Self {
+        Shape::arc(arc, [1.0, 1.0, 1.0], 0.0, bound)
+    }
 }
 
 #[repr(C)]
@@ -22,200 +593,1235 @@ pub struct Shape {
 pub struct Vertex {
     position: [f32; 2],
     color: [f32; 3],
-    z: f32
+    z: f32,
+    normal: [f32; 2],
+    alpha: f32,
+    uv: [f32; 2]
 }
 
 impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] =
-        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x3, 2 => Float32];
+    pub fn new(position: [f32; 2], color: [f32; 3], z: f32) -> Self {
+        Vertex{position, color, z, normal: [0.0, 0.0], alpha: 1.0, uv: [0.0, 0.0]}
+    }
+
+    pub fn position(&self) -> [f32; 2] {self.position}
+    pub fn color(&self) -> [f32; 3] {self.color}
+    pub fn z(&self) -> f32 {self.z}
+    pub fn normal(&self) -> [f32; 2] {self.normal}
+    /// Opaque for tessellated shapes, lower in shadows, or the shape's
+    /// material id divided by 255.
+    pub fn alpha(&self) -> f32 {self.alpha}
+    /// Zero unless the layout provides texture coordinates.
+    pub fn uv(&self) -> [f32; 2] {self.uv}
+
+    pub fn set_position(&mut self, position: [f32; 2]) {self.position = position;}
+    pub fn set_color(&mut self, color: [f32; 3]) {self.color = color;}
+    pub fn set_z(&mut self, z: f32) {self.z = z;}
+
+    fn is_finite(&self) -> bool {
+        self.position.iter().chain(&self.color).chain([&self.z]).chain(&self.uv).chain(&self.normal)
+            .all(|value| value.is_finite())
+    }
+
+    /// The number of builder attributes of the default `VertexLayout::ColorZ`: RGB color then z.
+    pub const ATTRIBUTE_COUNT: usize = 4;
+
+    #[cfg(feature = "wgpu")]
+    const ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        0 => Float32x2, 1 => Float32x3, 2 => Float32, 3 => Float32x2, 4 => Float32, 5 => Float32x2
+    ];
+
+    /// Position then alpha, packed for `VertexLayout::Uniform`.
+    #[cfg(feature = "wgpu")]
+    const UNIFORM_ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x2, 4 => Float32];
+
+    /// Position, normal then alpha, packed for `VertexLayout::Uniform` with lighting.
+    #[cfg(feature = "wgpu")]
+    const UNIFORM_LIT_ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x2, 3 => Float32x2, 4 => Float32
+    ];
+
+    #[cfg(feature = "wgpu")]
+    fn gpu_size(gpu: GpuVertex) -> usize {
+        gpu.ranges().iter().map(ExactSizeIterator::len).sum()
+    }
+
+    #[cfg(feature = "wgpu")]
+    fn layout(gpu: GpuVertex) -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: Self::gpu_size(gpu) as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: match gpu {
+                GpuVertex::Full => &Self::ATTRIBS,
+                GpuVertex::WithoutUv => &Self::ATTRIBS[..5],
+                GpuVertex::Uniform{normals: false} => &Self::UNIFORM_ATTRIBS,
+                GpuVertex::Uniform{normals: true} => &Self::UNIFORM_LIT_ATTRIBS
+            },
+        }
+    }
+
+    /// The bytes uploaded for `vertices`, copied into `packed` unless every
+    /// field is uploaded.
+    #[cfg(feature = "wgpu")]
+    fn gpu_bytes<'a>(gpu: GpuVertex, vertices: &'a [Vertex], packed: &'a mut Vec<u8>) -> &'a [u8] {
+        let raw: &[u8] = bytemuck::cast_slice(vertices);
+        if gpu == GpuVertex::Full {return raw;}
+        packed.clear();
+        for vertex in raw.chunks_exact(std::mem::size_of::<Self>()) {
+            for range in gpu.ranges() {
+                packed.extend_from_slice(&vertex[range.clone()]);
+            }
+        }
+        packed
+    }
+}
+
+/// The fields of each vertex uploaded by a renderer, which leaves out those its
+/// shaders never read.
+#[cfg(feature = "wgpu")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GpuVertex {
+    /// Every field, for the layouts with texture coordinates.
+    Full,
+    WithoutUv,
+    /// Position and alpha, with the normal between them for lighting. Colors
+    /// and z come from the shape colors.
+    Uniform{normals: bool}
+}
+
+#[cfg(feature = "wgpu")]
+impl GpuVertex {
+    /// The byte ranges of a `Vertex` uploaded, in order.
+    #[allow(clippy::single_range_in_vec_init)]
+    fn ranges(self) -> &'static [std::ops::Range<usize>] {
+        use std::mem::{offset_of, size_of};
+        const POSITION: std::ops::Range<usize> = offset_of!(Vertex, position)..offset_of!(Vertex, color);
+        match self {
+            GpuVertex::Full => &[0..size_of::<Vertex>()],
+            GpuVertex::WithoutUv => &[0..offset_of!(Vertex, uv)],
+            GpuVertex::Uniform{normals: false} => &[POSITION, offset_of!(Vertex, alpha)..offset_of!(Vertex, uv)],
+            GpuVertex::Uniform{normals: true} => &[POSITION, offset_of!(Vertex, normal)..offset_of!(Vertex, uv)]
+        }
+    }
+}
+
+/// The builder attributes shapes pass after each position. Every layout but
+/// `Uniform` starts with RGB color, all but `Color` followed by z, and the
+/// attributes of all shapes in a frame must match the layout it is
+/// tessellated with. The crate's shape helpers use `ColorZ`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VertexLayout {
+    #[default]
+    ColorZ,
+    /// Followed by texture coordinates u and v, uploaded at location 5 for
+    /// custom shaders. Vertices of the other layouts leave them out.
+    ColorZUv,
+    /// Followed by u, v and a 2D normal, used by lighting instead of the
+    /// computed outline normals.
+    ColorZUvNormal,
+    /// RGB color alone, with z taken from `Shape::z` or 0 for every vertex.
+    Color,
+    /// No attributes at all. A renderer built with it colors each shape from
+    /// `LyonRenderer::set_shape_colors`, which also sets its z. Shapes that
+    /// pass attributes, like the crate's helpers, make lyon panic. Only the
+    /// position and alpha of each vertex are uploaded, at locations 0 and 4,
+    /// with the normal at location 3 when the renderer is built with lighting.
+    Uniform
+}
+
+impl VertexLayout {
+    const ALL: [VertexLayout; 5] = [
+        VertexLayout::ColorZ, VertexLayout::ColorZUv, VertexLayout::ColorZUvNormal, VertexLayout::Color, VertexLayout::Uniform
+    ];
 
-    fn layout() -> VertexBufferLayout<'static> {
-        VertexBufferLayout {
-            array_stride: std::mem::size_of::<Self>() as BufferAddress,
-            step_mode: VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
+    pub fn attribute_count(self) -> usize {
+        match self {
+            VertexLayout::ColorZ => Vertex::ATTRIBUTE_COUNT,
+            VertexLayout::ColorZUv => Vertex::ATTRIBUTE_COUNT + 2,
+            VertexLayout::ColorZUvNormal => Vertex::ATTRIBUTE_COUNT + 4,
+            VertexLayout::Color => Vertex::ATTRIBUTE_COUNT - 1,
+            VertexLayout::Uniform => 0
         }
     }
+
+    fn has_normals(self) -> bool {self == VertexLayout::ColorZUvNormal}
+
+    /// Check a builder's attribute count up front, e.g. with
+    /// `builder.num_attributes()`, instead of panicking on the first vertex.
+    pub fn check_attributes(self, num_attributes: usize) -> Result<(), LyonError> {
+        if num_attributes != self.attribute_count() {
+            return Err(LyonError::AttributeCountMismatch{expected: self.attribute_count(), got: num_attributes});
+        }
+        Ok(())
+    }
 }
 
+/// Builds vertices from the attributes of any `VertexLayout`, telling them
+/// apart by count.
 #[derive(Clone)]
 pub struct VertexConstructor;
+
+impl VertexConstructor {
+    /// Like `VertexLayout::check_attributes`, but accepts the count of any layout.
+    pub fn check_attributes(num_attributes: usize) -> Result<(), LyonError> {
+        if !VertexLayout::ALL.iter().any(|layout| layout.attribute_count() == num_attributes) {
+            return Err(LyonError::UnknownAttributeCount{got: num_attributes});
+        }
+        Ok(())
+    }
+}
+
 impl FillVertexConstructor<Vertex> for VertexConstructor {
     fn new_vertex(&mut self, mut vertex: FillVertex) -> Vertex {
-        let attrs: [f32; 4] = vertex.interpolated_attributes().try_into()
-            .expect("Expected builder attributes to be 3 f32's representing RGB color values. And one f32 representing zindex");
-        Vertex{
-            position: vertex.position().to_array(),
-            color: [attrs[0], attrs[1], attrs[2]],
-            z: attrs[3]
+        let position = vertex.position().to_array();
+        let attrs = vertex.interpolated_attributes();
+        if let Err(error) = Self::check_attributes(attrs.len()) {
+            panic!("{}, create the builder with builder_with_attributes(VertexLayout::attribute_count(), ..)", error);
+        }
+        attributes_vertex(position, attrs)
+    }
+}
+
+impl StrokeVertexConstructor<Vertex> for VertexConstructor {
+    fn new_vertex(&mut self, mut vertex: StrokeVertex) -> Vertex {
+        attributes_vertex(vertex.position().to_array(), vertex.interpolated_attributes())
+    }
+}
+
+/// Builds vertices like `VertexConstructor`, but records an attribute count
+/// matching no `VertexLayout` instead of panicking, for tessellating with
+/// lyon directly where a panic is not acceptable. Meanwhile vertices take
+/// what they can: fewer than 3 attributes give black vertices at z 0, and
+/// attributes past the known ones are ignored.
+///
+/// lyon cannot fail from inside a vertex constructor, so check `error` after
+/// `build` and discard the geometry if it is set.
+#[derive(Clone, Default)]
+pub struct TryVertexConstructor {
+    error: Option<LyonError>
+}
+
+impl TryVertexConstructor {
+    pub fn new() -> Self {Self::default()}
+
+    /// The mismatch of the first vertex with an unexpected attribute count.
+    pub fn error(&self) -> Option<&LyonError> {self.error.as_ref()}
+
+    /// Take the recorded mismatch, so the constructor can be reused.
+    pub fn take_error(&mut self) -> Result<(), LyonError> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+}
+
+/// Borrow the constructor for a `BuffersBuilder`, to read `error` after `build`.
+impl FillVertexConstructor<Vertex> for &mut TryVertexConstructor {
+    fn new_vertex(&mut self, vertex: FillVertex) -> Vertex {
+        (**self).new_vertex(vertex)
+    }
+}
+
+impl FillVertexConstructor<Vertex> for TryVertexConstructor {
+    fn new_vertex(&mut self, mut vertex: FillVertex) -> Vertex {
+        let position = vertex.position().to_array();
+        let attrs = vertex.interpolated_attributes();
+        if self.error.is_none() {
+            self.error = VertexConstructor::check_attributes(attrs.len()).err();
         }
+        attributes_vertex(position, attrs)
     }
 }
 
-pub struct LyonRenderer {
-    render_pipeline: RenderPipeline,
-    vertex_buffer_size: u64,
-    vertex_buffer: Buffer,
-    index_buffer_size: u64,
-    index_buffer: Buffer,
-    lyon_buffers: VertexBuffers<Vertex, u16>,
-    shape_buffer: Vec<(usize, usize, Bound)>
+/// A vertex from the attributes of any `VertexLayout`, or as much of them as
+/// there is.
+fn attributes_vertex(position: [f32; 2], attrs: &[f32]) -> Vertex {
+    let mut result = match *attrs {
+        [r, g, b, z, ..] => Vertex::new(position, [r, g, b], z),
+        [r, g, b] => Vertex::new(position, [r, g, b], 0.0),
+        _ => return Vertex::new(position, [0.0, 0.0, 0.0], 0.0)
+    };
+    let extra = attrs.get(Vertex::ATTRIBUTE_COUNT..).unwrap_or(&[]);
+    if let [u, v, ..] = *extra {
+        result.uv = [u, v];
+    }
+    if let [_, _, x, y] = *extra {
+        result.normal = [x, y];
+    }
+    result
 }
 
-impl LyonRenderer {
-    /// Create all unchanging resources here.
-    pub fn new(
-        device: &Device,
-        texture_format: &TextureFormat,
-        multisample: MultisampleState,
-        depth_stencil: Option<DepthStencilState>,
+/// Records the sources of every vertex before delegating to `VertexConstructor`.
+#[cfg(feature = "debug-attributes")]
+struct DebugVertexConstructor<'a> {
+    sources: &'a mut Vec<Vec<VertexSource>>
+}
+
+#[cfg(feature = "debug-attributes")]
+impl FillVertexConstructor<Vertex> for DebugVertexConstructor<'_> {
+    fn new_vertex(&mut self, vertex: FillVertex) -> Vertex {
+        self.sources.push(vertex.sources().collect());
+        FillVertexConstructor::new_vertex(&mut VertexConstructor, vertex)
+    }
+}
+
+#[cfg(feature = "debug-attributes")]
+impl StrokeVertexConstructor<Vertex> for DebugVertexConstructor<'_> {
+    fn new_vertex(&mut self, vertex: StrokeVertex) -> Vertex {
+        self.sources.push(vec![vertex.source()]);
+        StrokeVertexConstructor::new_vertex(&mut VertexConstructor, vertex)
+    }
+}
+
+/// Keeps only the position, never reading the builder attributes.
+struct PositionConstructor;
+
+impl FillVertexConstructor<[f32; 2]> for PositionConstructor {
+    fn new_vertex(&mut self, vertex: FillVertex) -> [f32; 2] {
+        vertex.position().to_array()
+    }
+}
+
+impl StrokeVertexConstructor<[f32; 2]> for PositionConstructor {
+    fn new_vertex(&mut self, vertex: StrokeVertex) -> [f32; 2] {
+        vertex.position().to_array()
+    }
+}
+
+/// Tessellate shapes to positions and indices alone, e.g. for collision,
+/// without building full vertices. Shapes are filled whatever their style,
+/// or stroked along their `stroke_path`, shadows are not included and the
+/// indices are shared by every shape, so the shapes together can have at
+/// most 65536 vertices.
+pub fn tessellate_positions(shapes: &[Shape], fill_options: &FillOptions) -> Result<(Vec<[f32; 2]>, Vec<u16>), LyonError> {
+    tessellate_positions_with_layout(shapes, fill_options, VertexLayout::ColorZ)
+}
+
+/// Like `tessellate_positions`, for shapes that provide the builder attributes
+/// of `layout`, such as `VertexLayout::Uniform` for shapes without any.
+pub fn tessellate_positions_with_layout(
+    shapes: &[Shape],
+    fill_options: &FillOptions,
+    layout: VertexLayout
+) -> Result<(Vec<[f32; 2]>, Vec<u16>), LyonError> {
+    let mut buffers: VertexBuffers<[f32; 2], u16> = VertexBuffers::new();
+    let mut tessellator = FillTessellator::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        let mut buffer = BuffersBuilder::new(&mut buffers, PositionConstructor);
+        let result = match &shape.stroke_path {
+            Some((path, options)) => StrokeTessellator::new().tessellate_path(path, options, &mut buffer),
+            None => {
+                let mut builder = tessellator.builder_with_attributes(layout.attribute_count(), fill_options, &mut buffer);
+                (shape.constructor)(&mut builder);
+                builder.build()
+            }
+        };
+        result.map_err(|error| LyonError::Tessellation{shape: i, error})?;
+    }
+    Ok((buffers.vertices, buffers.indices))
+}
+
+/// Appends shapes to shared buffers like lyon's `BuffersBuilder`, but numbers
+/// each shape's vertices from its own first vertex. Every shape can then use
+/// the whole `u16` range however many vertices the frame already holds.
+struct ShapeBuffersBuilder<'a, C> {
+    buffers: &'a mut VertexBuffers<Vertex, u16>,
+    base_vertex: usize,
+    start: usize,
+    constructor: C
+}
+
+impl<'a, C> ShapeBuffersBuilder<'a, C> {
+    fn new(buffers: &'a mut VertexBuffers<Vertex, u16>, constructor: C) -> Self {
+        let (base_vertex, start) = (buffers.vertices.len(), buffers.indices.len());
+        ShapeBuffersBuilder{buffers, base_vertex, start, constructor}
+    }
+}
+
+impl<C> GeometryBuilder for ShapeBuffersBuilder<'_, C> {
+    fn begin_geometry(&mut self) {
+        self.base_vertex = self.buffers.vertices.len();
+        self.start = self.buffers.indices.len();
+    }
+
+    fn add_triangle(&mut self, a: VertexId, b: VertexId, c: VertexId) {
+        self.buffers.indices.extend([a, b, c].map(|id| id.0 as u16));
+    }
+
+    fn abort_geometry(&mut self) {
+        self.buffers.vertices.truncate(self.base_vertex);
+        self.buffers.indices.truncate(self.start);
+    }
+}
+
+impl<C> ShapeBuffersBuilder<'_, C> {
+    fn push_vertex(&mut self, vertex: Vertex) -> Result<VertexId, GeometryBuilderError> {
+        let id = self.buffers.vertices.len() - self.base_vertex;
+        if id > u16::MAX as usize {
+            return Err(GeometryBuilderError::TooManyVertices);
+        }
+        self.buffers.vertices.push(vertex);
+        Ok(VertexId(id as u32))
+    }
+}
+
+impl<C: FillVertexConstructor<Vertex>> FillGeometryBuilder for ShapeBuffersBuilder<'_, C> {
+    fn add_fill_vertex(&mut self, vertex: FillVertex) -> Result<VertexId, GeometryBuilderError> {
+        let vertex = self.constructor.new_vertex(vertex);
+        self.push_vertex(vertex)
+    }
+}
+
+impl<C: StrokeVertexConstructor<Vertex>> StrokeGeometryBuilder for ShapeBuffersBuilder<'_, C> {
+    fn add_stroke_vertex(&mut self, vertex: StrokeVertex) -> Result<VertexId, GeometryBuilderError> {
+        let vertex = self.constructor.new_vertex(vertex);
+        self.push_vertex(vertex)
+    }
+}
+
+/// How many target pixels a shape unit covers through the coordinate transform
+/// and `to_pixels`, as the square root of the area scale.
+fn pixel_scale(to_pixels: &Transform, transform: Option<[[f32; 3]; 3]>) -> f32 {
+    let transform = transform.map_or(1.0, |m| ((m[0][0] * m[1][1] - m[1][0] * m[0][1]) / (m[2][2] * m[2][2])).abs());
+    let to_pixels = (to_pixels.m11 * to_pixels.m22 - to_pixels.m12 * to_pixels.m21).abs();
+    (transform * to_pixels).sqrt()
+}
+
+/// Apply a column major 2D homogeneous transform, dividing by w.
+fn transform_point(m: &[[f32; 3]; 3], [x, y]: [f32; 2]) -> [f32; 2] {
+    let w = m[0][2] * x + m[1][2] * y + m[2][2];
+    [(m[0][0] * x + m[1][0] * y + m[2][0]) / w, (m[0][1] * x + m[1][1] * y + m[2][1]) / w]
+}
+
+/// The overlap of two bounds, or `None` if they do not overlap.
+fn intersect_bounds(a: Bound, b: Bound) -> Option<Bound> {
+    let x = a.0.max(b.0);
+    let y = a.1.max(b.1);
+    let right = a.0.saturating_add(a.2).min(b.0.saturating_add(b.2));
+    let bottom = a.1.saturating_add(a.3).min(b.1.saturating_add(b.3));
+    (right > x && bottom > y).then(|| (x, y, right - x, bottom - y))
+}
+
+/// The parts of `a` outside `b`, as up to four disjoint bounds.
+fn subtract_bound(a: Bound, b: Bound) -> Vec<Bound> {
+    let Some((x, y, width, height)) = intersect_bounds(a, b) else {return vec![a];};
+    let (right, bottom) = (a.0.saturating_add(a.2), a.1.saturating_add(a.3));
+    let mut parts = Vec::with_capacity(4);
+    //Full width above and below the overlap, then either side of it
+    if y > a.1 {parts.push((a.0, a.1, a.2, y - a.1));}
+    if y + height < bottom {parts.push((a.0, y + height, a.2, bottom - y - height));}
+    if x > a.0 {parts.push((a.0, y, x - a.0, height));}
+    if x + width < right {parts.push((x + width, y, right - x - width, height));}
+    parts
+}
+
+/// A single indexed draw; indices in `start..end` are relative to `base_vertex`.
+#[derive(Clone, Copy)]
+struct DrawCall {
+    start: u32,
+    end: u32,
+    base_vertex: i32,
+    bound: Bound,
+    normalized: Option<[f32; 4]>,
+    /// The range of the frame's `union_bounds` drawn in besides `bound`.
+    union: (u32, u32),
+    /// The number of indices at `start` drawing the shadow, before the shape's own.
+    shadow_indices: u32,
+    /// The `start`, `end` and `base_vertex` of the shape's clip mask, tessellated
+    /// after the shape.
+    mask: Option<(u32, u32, i32)>,
+    /// The index of the shape's transform in the frame's `transforms`.
+    transform: Option<u32>,
+    /// Whether every vertex, shadows included, is fully opaque.
+    opaque: bool,
+    /// The middle of the shape's z range, used to sort shapes by depth.
+    z: f32
+}
+
+impl DrawCall {
+    /// The draw of a shape's finished `vertices`.
+    fn new(
+        start: u32,
+        end: u32,
+        base_vertex: usize,
+        bound: Bound,
+        normalized: Option<[f32; 4]>,
+        union: (u32, u32),
+        vertices: &[Vertex]
     ) -> Self {
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor::default());
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                compilation_options: PipelineCompilationOptions::default(),
-                buffers: &[
-                    Vertex::layout()
-                ]
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                compilation_options: PipelineCompilationOptions::default(),
-                targets: &[Some((*texture_format).into())],
-            }),
-            primitive: PrimitiveState::default(),
-            depth_stencil,
-            multisample,
-            multiview: None,
-            cache: None
-        });
+        let mut draw = DrawCall{
+            start, end, base_vertex: base_vertex as i32, bound, normalized, union, shadow_indices: 0, mask: None, transform: None, opaque: true, z: 0.0
+        };
+        draw.update_depth(vertices);
+        draw
+    }
 
-        let vertex_buffer_size = Self::next_copy_buffer_size(4096);
-        let vertex_buffer = device.create_buffer(&BufferDescriptor {
-            label: None,
-            size: vertex_buffer_size,
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
+    /// Take `opaque` and `z` from the shape's current `vertices`.
+    fn update_depth(&mut self, vertices: &[Vertex]) {
+        let (min_z, max_z) = vertices.iter().fold((f32::MAX, f32::MIN), |(min, max), vertex| {
+            (min.min(vertex.z), max.max(vertex.z))
         });
+        self.opaque = vertices.iter().all(|vertex| vertex.alpha >= 1.0);
+        self.z = if vertices.is_empty() {0.0} else {(min_z + max_z) / 2.0};
+    }
+}
 
-        let index_buffer_size = Self::next_copy_buffer_size(4096);
-        let index_buffer = device.create_buffer(&BufferDescriptor {
-            label: None,
-            size: index_buffer_size,
-            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+/// Everything but the shapes that decides how a frame is tessellated.
+#[derive(Clone, Copy)]
+struct TessellationSettings<'a> {
+    fill_options: &'a FillOptions,
+    clips: &'a [Bound],
+    normals: bool,
+    sanitize: bool,
+    layout: VertexLayout,
+    transform: Option<[[f32; 3]; 3]>,
+    quantization: Option<f32>,
+    /// From positions to target pixels, for shapes without anti-aliasing.
+    pixel_grid: Option<Transform>
+}
+
+impl<'a> TessellationSettings<'a> {
+    fn new(fill_options: &'a FillOptions, layout: VertexLayout) -> Self {
+        TessellationSettings{fill_options, clips: &[], normals: false, sanitize: false, layout, transform: None, quantization: None, pixel_grid: None}
+    }
+}
 
-        let lyon_buffers: VertexBuffers<Vertex, u16> = VertexBuffers::new();
-        LyonRenderer{
-            render_pipeline,
-            vertex_buffer_size,
-            vertex_buffer,
-            index_buffer_size,
-            index_buffer,
-            lyon_buffers,
-            shape_buffer: Vec::new()
+/// Geometry tessellated on the CPU, ready to be uploaded by a renderer.
+pub struct TessellatedFrame {
+    buffers: VertexBuffers<Vertex, u16>,
+    draws: Vec<DrawCall>,
+    /// The disjoint union bounds of every shape, clipped like their `bound`.
+    union_bounds: Vec<Bound>,
+    /// The transforms of shapes drawn through their own matrix.
+    transforms: Vec<[[f32; 4]; 4]>,
+    skipped: Vec<usize>,
+    #[cfg(feature = "debug-attributes")]
+    sources: Vec<Vec<VertexSource>>
+}
+
+impl Default for TessellatedFrame {
+    fn default() -> Self {
+        TessellatedFrame{
+            buffers: VertexBuffers::new(),
+            draws: Vec::new(),
+            union_bounds: Vec::new(),
+            transforms: Vec::new(),
+            skipped: Vec::new(),
+            #[cfg(feature = "debug-attributes")]
+            sources: Vec::new()
         }
     }
+}
+
+impl TessellatedFrame {
+    /// Append the shapes of `other`, drawn after the shapes of `self`.
+    ///
+    /// Indices are relative to each shape's first vertex, so they stay `u16`
+    /// even when the combined frame holds more than 65535 vertices. Fails when
+    /// the combined frame has more than `u32::MAX` indices or `i32::MAX`
+    /// vertices, with the first shape of `other` that does not fit.
+    pub fn merge(mut self, other: TessellatedFrame) -> Result<Self, LyonError> {
+        let shapes = self.draws.len();
+        let base_vertex = i32::try_from(self.buffers.vertices.len()).ok();
+        let start = u32::try_from(self.buffers.indices.len()).ok();
+        let union_start = u32::try_from(self.union_bounds.len()).ok();
+        let transform_start = u32::try_from(self.transforms.len()).ok();
+        let index = |index: u32| start?.checked_add(index);
+        let vertex = |vertex: i32| base_vertex?.checked_add(vertex);
+        let draws = other.draws.into_iter().enumerate().map(|(i, draw)| {
+            let offset = || Some(DrawCall{
+                start: index(draw.start)?,
+                end: index(draw.end)?,
+                base_vertex: vertex(draw.base_vertex)?,
+                bound: draw.bound,
+                normalized: draw.normalized,
+                union: (union_start?.checked_add(draw.union.0)?, union_start?.checked_add(draw.union.1)?),
+                mask: match draw.mask {
+                    Some((mask_start, mask_end, mask_base)) => Some((index(mask_start)?, index(mask_end)?, vertex(mask_base)?)),
+                    None => None
+                },
+                transform: match draw.transform {
+                    Some(transform) => Some(transform_start?.checked_add(transform)?),
+                    None => None
+                },
+                shadow_indices: draw.shadow_indices,
+                opaque: draw.opaque,
+                z: draw.z
+            });
+            offset().ok_or(LyonError::TooManyIndices{shape: shapes + i})
+        }).collect::<Result<Vec<_>, _>>()?;
+        self.draws.extend(draws);
+        self.union_bounds.extend(other.union_bounds);
+        self.transforms.extend(other.transforms);
+        self.skipped.extend(other.skipped.into_iter().map(|shape| shape + shapes));
+        self.buffers.vertices.extend(other.buffers.vertices);
+        self.buffers.indices.extend(other.buffers.indices);
+        #[cfg(feature = "debug-attributes")]
+        self.sources.extend(other.sources);
+        Ok(self)
+    }
+
+    /// Tessellate shapes without clipping them or computing normals. Use
+    /// `LyonRenderer::tessellate` to apply a renderer's clip stack and lighting.
+    pub fn new(fill_options: &FillOptions, shapes: Vec<Shape>) -> Result<Self, LyonError> {
+        Self::new_with_layout(fill_options, shapes, VertexLayout::ColorZ)
+    }
+
+    /// Like `new`, for shapes that provide the builder attributes of `layout`.
+    pub fn new_with_layout(fill_options: &FillOptions, shapes: Vec<Shape>, layout: VertexLayout) -> Result<Self, LyonError> {
+        let mut frame = TessellatedFrame::default();
+        frame.tessellate_into(&mut FillTessellator::new(), shapes, TessellationSettings::new(fill_options, layout))?;
+        Ok(frame)
+    }
+
+    /// Like `new`, tessellating on `threads` scoped threads with 0 using every core.
+    pub fn new_threaded(fill_options: &FillOptions, shapes: Vec<Shape>, threads: usize) -> Result<Self, LyonError> {
+        let mut frame = TessellatedFrame::default();
+        let settings = TessellationSettings::new(fill_options, VertexLayout::ColorZ);
+        frame.tessellate_threaded(&mut FillTessellator::new(), shapes, settings, threads)?;
+        Ok(frame)
+    }
 
-    /// Prepare for rendering this frame; create all resources that will be
-    /// used during the next render that do not already exist.
-    pub fn prepare(
+    /// Like `tessellate_into`, splitting the shapes into one chunk per thread and
+    /// merging the chunks in order, so the result is the same as tessellating
+    /// them one after another. `threads` of 0 uses every core and 1 stays on
+    /// the current thread.
+    fn tessellate_threaded<S: Borrow<Shape> + Send>(
         &mut self,
-        device: &Device,
-        queue: &Queue,
-        fill_options: &FillOptions,
-        shapes: Vec<Shape>
-    ) {
-        self.lyon_buffers.clear();
-        self.shape_buffer.clear();
+        tessellator: &mut FillTessellator,
+        shapes: impl IntoIterator<Item = S>,
+        settings: TessellationSettings<'_>,
+        threads: usize
+    ) -> Result<(), LyonError> {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads
+        };
+        let shapes: Vec<S> = shapes.into_iter().collect();
+        if threads == 1 || shapes.len() < 2 {
+            return self.tessellate_into(tessellator, shapes, settings);
+        }
 
-        let mut index = 0;
+        let chunk_size = shapes.len().div_ceil(threads);
+        let mut chunks: Vec<Vec<S>> = Vec::with_capacity(threads);
+        for (i, shape) in shapes.into_iter().enumerate() {
+            if i % chunk_size == 0 {
+                chunks.push(Vec::with_capacity(chunk_size));
+            }
+            chunks.last_mut().unwrap().push(shape);
+        }
 
-        let mut buffer = BuffersBuilder::new(&mut self.lyon_buffers, VertexConstructor);
-        let mut tessellator = FillTessellator::new();
-        for shape in shapes {
-            let mut builder = tessellator.builder_with_attributes(4, fill_options, &mut buffer);
-            (shape.constructor)(&mut builder);
-            builder.build().unwrap();
+        let mut chunks = chunks.into_iter();
+        let first = chunks.next().unwrap();
+        let frames = std::thread::scope(|scope| {
+            let handles: Vec<_> = chunks.map(|chunk| scope.spawn(move || {
+                let mut frame = TessellatedFrame::default();
+                frame.tessellate_into(&mut FillTessellator::new(), chunk, settings).map(|()| frame)
+            })).collect();
+            //The first chunk runs here, reusing the caller's tessellator
+            let mut frame = TessellatedFrame::default();
+            let first = frame.tessellate_into(tessellator, first, settings).map(|()| frame);
+            std::iter::once(first).chain(handles.into_iter().map(|handle| {
+                handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })).collect::<Vec<_>>()
+        });
 
-            let buffer_len = buffer.buffers().indices.len();
-            self.shape_buffer.push((index, buffer_len, shape.bound));
-            index = buffer_len;
+        for frame in frames {
+            let offset = self.draws.len();
+            let frame = frame.map_err(|error| error.offset_shape(offset))?;
+            *self = std::mem::take(self).merge(frame)?;
         }
+        Ok(())
+    }
+
+    /// Append the shapes clipped by `clips`, computing normals for lighting
+    /// when `normals` is set and the layout does not provide them. With
+    /// `sanitize`, shapes that fail to tessellate or produce non-finite
+    /// vertices are left empty and recorded as skipped.
+    fn tessellate_into<S: Borrow<Shape>>(
+        &mut self,
+        tessellator: &mut FillTessellator,
+        shapes: impl IntoIterator<Item = S>,
+        settings: TessellationSettings<'_>
+    ) -> Result<(), LyonError> {
+        let TessellationSettings{fill_options, clips, normals, sanitize, layout, transform, quantization, pixel_grid} = settings;
+        let pixel_grid = pixel_grid.and_then(|to_pixels| Some((to_pixels, to_pixels.inverse()?)));
+        let normals = normals && !layout.has_normals();
+        let first_draw = self.draws.len();
+        for (i, shape) in shapes.into_iter().enumerate() {
+            let shape = shape.borrow();
+            //Already validated by the previous shape
+            let start = self.buffers.indices.len() as u32;
+            let base_vertex = self.buffers.vertices.len();
+            //Normalized bounds are only known in pixels at render time
+            let normalized = shape.normalized_bound;
+            let bound = if normalized.is_some() {(0, 0, u32::MAX, u32::MAX)} else {shape.bound};
 
-        if self.lyon_buffers.vertices.is_empty() || self.lyon_buffers.indices.is_empty() {return;}
+            let clipped = clips.iter().try_fold(bound, |bound, clip| intersect_bounds(bound, *clip));
+            let union_start = self.union_bounds.len();
+            for union_bound in shape.union_bounds.iter().filter(|_| normalized.is_none()) {
+                let Some(union_bound) = clips.iter().try_fold(*union_bound, |bound, clip| intersect_bounds(bound, *clip))
+                    else {continue;};
+                let parts = clipped.iter().chain(&self.union_bounds[union_start..]).fold(vec![union_bound], |parts, drawn| {
+                    parts.into_iter().flat_map(|part| subtract_bound(part, *drawn)).collect()
+                });
+                self.union_bounds.extend(parts);
+            }
+            let union = (union_start as u32, self.union_bounds.len() as u32);
+            if clipped.is_none() && union.0 == union.1 && bound.2 != 0 && bound.3 != 0 {
+                //Clipped away entirely, keep an empty draw so shape indices still line up
+                self.draws.push(DrawCall::new(start, start, base_vertex, bound, normalized, union, &[]));
+                continue;
+            }
 
-        let vertices_raw = bytemuck::cast_slice(&self.lyon_buffers.vertices);
-        if self.vertex_buffer_size >= vertices_raw.len() as u64 {
-            Self::write_buffer(queue, &self.vertex_buffer, vertices_raw);
-        } else {
-            let (vertex_buffer, vertex_buffer_size) = Self::create_oversized_buffer(
-                device, None, vertices_raw, BufferUsages::VERTEX | BufferUsages::COPY_DST
+            let stroke_scale = match (shape.stroke_width_space, pixel_grid) {
+                (StrokeWidthSpace::Screen, Some((to_pixels, _))) => 1.0 / pixel_scale(&to_pixels, transform),
+                _ => 1.0
+            };
+            let result = {
+                #[cfg(not(feature = "debug-attributes"))]
+                let mut buffer = ShapeBuffersBuilder::new(&mut self.buffers, VertexConstructor);
+                #[cfg(feature = "debug-attributes")]
+                let mut buffer = ShapeBuffersBuilder::new(
+                    &mut self.buffers, DebugVertexConstructor{sources: &mut self.sources}
+                );
+                match &shape.stroke_path {
+                    Some((path, options)) => {
+                        let options = options.with_line_width(options.line_width * stroke_scale);
+                        StrokeTessellator::new().tessellate_path(path, &options, &mut buffer)
+                    }
+                    None => {
+                        let mut builder = tessellator.builder_with_attributes(layout.attribute_count(), fill_options, &mut buffer);
+                        (shape.constructor)(&mut builder);
+                        builder.build()
+                    }
+                }
+            };
+            let result = result.and_then(|()| match shape.style.with_line_width_scale(stroke_scale) {
+                ShapeStyle::Fill => Ok(()),
+                ShapeStyle::Stroke(options) => stroke_outline(&mut self.buffers, base_vertex, start as usize, &options, false),
+                ShapeStyle::Both(options) => stroke_outline(&mut self.buffers, base_vertex, start as usize, &options, true)
+            });
+            #[cfg(feature = "debug-attributes")]
+            {
+                //Stroke vertices come from no single source
+                if result.is_ok() && matches!(shape.style, ShapeStyle::Stroke(_)) {
+                    self.sources.truncate(base_vertex);
+                }
+                self.sources.resize(self.buffers.vertices.len(), Vec::new());
+            }
+            if let Some(z) = shape.z {
+                for vertex in &mut self.buffers.vertices[base_vertex..] {
+                    vertex.z = z;
+                }
+            }
+            if let Some(transform) = transform {
+                for vertex in &mut self.buffers.vertices[base_vertex..] {
+                    vertex.position = transform_point(&transform, vertex.position);
+                }
+            }
+
+            if sanitize && (result.is_err() || !self.buffers.vertices[base_vertex..].iter().all(Vertex::is_finite)) {
+                self.skip_shape(i, start, base_vertex, union_start, bound, normalized);
+                continue;
+            }
+            result.map_err(|error| LyonError::Tessellation{shape: i, error})?;
+            let fill_indices = self.buffers.indices.len();
+            if let Some(shadow) = &shape.shadow {
+                let _origins = add_shadow(&mut self.buffers, base_vertex, start as usize, shadow)
+                    .map_err(|error| LyonError::Tessellation{shape: i, error})?;
+                #[cfg(feature = "debug-attributes")]
+                for origin in _origins {
+                    self.sources.push(self.sources[base_vertex + origin].clone());
+                }
+            }
+            snap_positions(&mut self.buffers.vertices[base_vertex..], quantization, pixel_grid.filter(|_| !shape.antialias));
+
+            let end = u32::try_from(self.buffers.indices.len())
+                .map_err(|_| LyonError::TooManyIndices{shape: i})?;
+            if end > start && union.0 == union.1 && (bound.2 == 0 || bound.3 == 0) {
+                return Err(LyonError::EmptyBound{shape: i, bound});
+            }
+            if let Some(bound @ [_, _, width, height]) = normalized {
+                if end > start && !(width > 0.0 && height > 0.0) {
+                    return Err(LyonError::EmptyNormalizedBound{shape: i, bound});
+                }
+            }
+
+            //Only the union bounds are left to draw in when `bound` is clipped away
+            let bound = clipped.unwrap_or((0, 0, 0, 0));
+            let mut draw = DrawCall::new(
+                start, end, base_vertex, bound, normalized, union, &self.buffers.vertices[base_vertex..]
             );
-            self.vertex_buffer = vertex_buffer;
-            self.vertex_buffer_size = vertex_buffer_size;
+            draw.shadow_indices = (self.buffers.indices.len() - fill_indices) as u32;
+            self.draws.push(draw);
+            //After the draw takes its opacity from the real alpha
+            if let Some(material_id) = shape.material_id {
+                for vertex in &mut self.buffers.vertices[base_vertex..] {
+                    vertex.alpha = material_id as f32 / 255.0;
+                }
+            }
+
+            if let Some(mask) = &shape.clip_mask {
+                let mask_start = self.buffers.indices.len();
+                let mask_base = self.buffers.vertices.len();
+                let result = self.tessellate_mask(tessellator, i, mask, settings, pixel_grid);
+                if sanitize && (result.is_err() || !self.buffers.vertices[mask_base..].iter().all(Vertex::is_finite)) {
+                    let draw = self.draws.pop().unwrap();
+                    self.skip_shape(i, start, base_vertex, union_start, draw.bound, normalized);
+                    continue;
+                }
+                result?;
+                let mask_end = u32::try_from(self.buffers.indices.len())
+                    .map_err(|_| LyonError::TooManyIndices{shape: i})?;
+                self.draws.last_mut().unwrap().mask = Some((mask_start as u32, mask_end, mask_base as i32));
+            }
+            if let Some(transform) = shape.transform {
+                self.draws.last_mut().unwrap().transform = Some(self.transforms.len() as u32);
+                self.transforms.push(transform);
+            }
         }
 
-        let indices_raw = bytemuck::cast_slice(&self.lyon_buffers.indices);
-        if self.index_buffer_size >= indices_raw.len() as u64 {
-            Self::write_buffer(queue, &self.index_buffer, indices_raw);
-        } else {
-            let (index_buffer, index_buffer_size) = Self::create_oversized_buffer(
-                device, None, indices_raw, BufferUsages::INDEX | BufferUsages::COPY_DST
-            );
-            self.index_buffer = index_buffer;
-            self.index_buffer_size = index_buffer_size;
+        if normals {
+            for (i, draw) in self.draws.iter().enumerate().skip(first_draw) {
+                let base_vertex = draw.base_vertex as usize;
+                let vertex_end = self.draws.get(i+1)
+                    .map_or(self.buffers.vertices.len(), |next| next.base_vertex as usize);
+                compute_normals(
+                    &mut self.buffers.vertices[base_vertex..vertex_end],
+                    &self.buffers.indices[draw.start as usize..draw.end as usize]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Append already triangulated `vertices`, three per triangle, as a shape
+    /// drawn after the others and scissored to `bound`, e.g. for debug gizmos.
+    /// The clip stack, lighting normals and the renderer's coordinate transform
+    /// are not applied. Upload the frame with `LyonRenderer::upload`.
+    pub fn push_triangles(&mut self, vertices: &[Vertex], bound: Bound) -> Result<(), LyonError> {
+        let shape = self.draws.len();
+        if !vertices.len().is_multiple_of(3) {
+            return Err(LyonError::IncompleteTriangle{shape, vertices: vertices.len()});
+        }
+        if vertices.len() > u16::MAX as usize + 1 {
+            let error = TessellationError::GeometryBuilder(GeometryBuilderError::TooManyVertices);
+            return Err(LyonError::Tessellation{shape, error});
+        }
+        let start = self.buffers.indices.len() as u32;
+        let end = u32::try_from(self.buffers.indices.len() + vertices.len())
+            .map_err(|_| LyonError::TooManyIndices{shape})?;
+        if end > start && (bound.2 == 0 || bound.3 == 0) {
+            return Err(LyonError::EmptyBound{shape, bound});
         }
+        let base_vertex = self.buffers.vertices.len();
+        self.buffers.vertices.extend_from_slice(vertices);
+        self.buffers.indices.extend((0..vertices.len()).map(|i| i as u16));
+        #[cfg(feature = "debug-attributes")]
+        self.sources.resize(self.buffers.vertices.len(), Vec::new());
+        let union = (self.union_bounds.len() as u32, self.union_bounds.len() as u32);
+        self.draws.push(DrawCall::new(start, end, base_vertex, bound, None, union, vertices));
+        Ok(())
     }
 
-    /// Render using caller provided render pass.
-    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
-        if self.lyon_buffers.vertices.is_empty() || self.lyon_buffers.indices.is_empty() {return;}
+    /// Drop what was added for shape `i` and keep an empty draw in its place.
+    fn skip_shape(
+        &mut self,
+        i: usize,
+        start: u32,
+        base_vertex: usize,
+        union_start: usize,
+        bound: Bound,
+        normalized: Option<[f32; 4]>
+    ) {
+        self.buffers.vertices.truncate(base_vertex);
+        self.buffers.indices.truncate(start as usize);
+        #[cfg(feature = "debug-attributes")]
+        self.sources.truncate(base_vertex);
+        self.union_bounds.truncate(union_start);
+        self.skipped.push(i);
+        let union = (union_start as u32, union_start as u32);
+        self.draws.push(DrawCall::new(start, start, base_vertex, bound, normalized, union, &[]));
+    }
+
+    /// Fill `mask` with the even-odd rule after the last shape, positioned like
+    /// shape `i`.
+    fn tessellate_mask(
+        &mut self,
+        tessellator: &mut FillTessellator,
+        i: usize,
+        mask: &Shape,
+        settings: TessellationSettings<'_>,
+        pixel_grid: Option<(Transform, Transform)>
+    ) -> Result<(), LyonError> {
+        let TessellationSettings{fill_options, layout, transform, quantization, ..} = settings;
+        let base_vertex = self.buffers.vertices.len();
+        let fill_options = fill_options.with_fill_rule(FillRule::EvenOdd);
+        {
+            #[cfg(not(feature = "debug-attributes"))]
+            let mut buffer = ShapeBuffersBuilder::new(&mut self.buffers, VertexConstructor);
+            #[cfg(feature = "debug-attributes")]
+            let mut buffer = ShapeBuffersBuilder::new(
+                &mut self.buffers, DebugVertexConstructor{sources: &mut self.sources}
+            );
+            let mut builder = tessellator.builder_with_attributes(layout.attribute_count(), &fill_options, &mut buffer);
+            (mask.constructor)(&mut builder);
+            builder.build().map_err(|error| LyonError::Tessellation{shape: i, error})?;
+        }
+        if let Some(transform) = transform {
+            for vertex in &mut self.buffers.vertices[base_vertex..] {
+                vertex.position = transform_point(&transform, vertex.position);
+            }
+        }
+        snap_positions(&mut self.buffers.vertices[base_vertex..], quantization, pixel_grid.filter(|_| !mask.antialias));
+        Ok(())
+    }
 
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
-        for (start, end, bound) in &self.shape_buffer {
-            render_pass.set_scissor_rect(bound.0, bound.1, bound.2, bound.3);
-            render_pass.draw_indexed(*start as u32..*end as u32, 0, 0..1);
+    /// Run `f` over every vertex, e.g. to distort the geometry before uploading
+    /// it. Sorting by depth follows changes to z.
+    pub fn map_vertices(&mut self, mut f: impl FnMut(&mut Vertex)) {
+        self.buffers.vertices.iter_mut().for_each(&mut f);
+        for i in 0..self.draws.len() {
+            let base_vertex = self.draws[i].base_vertex as usize;
+            let vertex_end = self.draws[i].mask.map(|(_, _, mask_base)| mask_base as usize)
+                .or(self.draws.get(i+1).map(|next| next.base_vertex as usize))
+                .unwrap_or(self.buffers.vertices.len());
+            self.draws[i].update_depth(&self.buffers.vertices[base_vertex..vertex_end]);
         }
     }
 
-    fn write_buffer(queue: &Queue, buffer: &Buffer, slice: &[u8]) {
-        let pad: usize = slice.len() % 4;
-        let slice = if pad != 0 {
-            &[slice, &vec![0u8; pad]].concat()
-        } else {slice};
-        queue.write_buffer(buffer, 0, slice);
+    /// Every triangle in draw order, with each shape's relative `u16` indices
+    /// resolved against its first vertex.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        self.draws.iter().flat_map(move |draw| {
+            let vertices = &self.buffers.vertices[draw.base_vertex as usize..];
+            self.buffers.indices[draw.start as usize..draw.end as usize].chunks_exact(3)
+                .map(move |triangle| [0, 1, 2].map(|i| vertices[triangle[i] as usize]))
+        })
+    }
+
+    /// The indices of shapes skipped by sanitizing.
+    pub fn skipped_shapes(&self) -> &[usize] {&self.skipped}
+
+    /// Write the frame's triangles to an SVG file as polygons filled with the
+    /// average of their vertex colors and alphas and thinly outlined to show
+    /// the mesh, y up as in clip space, e.g. to inspect it in a browser. Past
+    /// `MAX_SVG_TRIANGLES` the rest are left out, noted in a comment.
+    #[cfg(feature = "debug-export")]
+    pub fn export_svg(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        write_svg(self.triangles(), path.as_ref())
+    }
+
+    /// Whether the frame has nothing to draw.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.vertices.is_empty() || self.buffers.indices.is_empty()
+    }
+
+    /// Drop the vertices and indices with their allocations, keeping the
+    /// draws, once they are uploaded.
+    #[cfg(feature = "wgpu")]
+    pub(crate) fn free_geometry(&mut self) {
+        self.buffers = VertexBuffers::new();
+        #[cfg(feature = "debug-attributes")]
+        {self.sources = Vec::new();}
     }
 
-    fn next_copy_buffer_size(size: u64) -> u64 {
-        let align_mask = COPY_BUFFER_ALIGNMENT - 1;
-        ((size.next_power_of_two() + align_mask) & !align_mask).max(COPY_BUFFER_ALIGNMENT)
+    /// Remove every shape, keeping the allocations.
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+        self.draws.clear();
+        self.union_bounds.clear();
+        self.transforms.clear();
+        self.skipped.clear();
+        #[cfg(feature = "debug-attributes")]
+        self.sources.clear();
     }
+}
+
+/// The most triangles `export_svg` writes, so the files of huge meshes still
+/// open in a browser.
+#[cfg(feature = "debug-export")]
+pub const MAX_SVG_TRIANGLES: usize = 100_000;
+
+#[cfg(feature = "debug-export")]
+fn write_svg(triangles: impl Iterator<Item = [Vertex; 3]>, path: &std::path::Path) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut triangles = triangles;
+    let kept: Vec<[Vertex; 3]> = triangles.by_ref().take(MAX_SVG_TRIANGLES).collect();
+    let left_out = triangles.count();
+    let bounds = Box2D::from_points(kept.iter().flatten().map(|vertex| Point::new(vertex.position[0], -vertex.position[1])));
+    let (origin, size) = if kept.is_empty() {
+        (Point::new(-1.0, -1.0), Vector::new(2.0, 2.0))
+    } else {
+        (bounds.min, (bounds.max - bounds.min).max(Vector::splat(f32::EPSILON)))
+    };
 
-    fn create_oversized_buffer(
-        device: &Device,
-        label: Option<&str>,
-        contents: &[u8],
-        usage: BufferUsages,
-    ) -> (Buffer, u64) {
-        let size = Self::next_copy_buffer_size(contents.len() as u64);
-        let buffer = device.create_buffer(&BufferDescriptor {
-            label,
-            size,
-            usage,
-            mapped_at_creation: true,
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#, origin.x, origin.y, size.x, size.y)?;
+    if left_out > 0 {
+        writeln!(file, "<!-- {} more triangles left out -->", left_out)?;
+    }
+    writeln!(file, r#"<g stroke="black" stroke-opacity="0.25" stroke-width="{}">"#, size.x.max(size.y) / 1000.0)?;
+    for triangle in &kept {
+        let [r, g, b] = [0, 1, 2].map(|channel| {
+            let value = triangle.iter().map(|vertex| vertex.color[channel]).sum::<f32>() / 3.0;
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
         });
-        buffer.slice(..).get_mapped_range_mut()[..contents.len()].copy_from_slice(contents);
-        buffer.unmap();
-        (buffer, size)
+        let alpha = triangle.iter().map(|vertex| vertex.alpha).sum::<f32>() / 3.0;
+        let [p0, p1, p2] = triangle.map(|vertex| vertex.position);
+        write!(
+            file, r#"<polygon points="{},{} {},{} {},{}" fill="rgb({},{},{})""#,
+            p0[0], -p0[1], p1[0], -p1[1], p2[0], -p2[1], r, g, b
+        )?;
+        if alpha < 1.0 {
+            write!(file, r#" fill-opacity="{}""#, alpha.max(0.0))?;
+        }
+        writeln!(file, "/>")?;
+    }
+    writeln!(file, "</g>\n</svg>")?;
+    file.flush()
+}
+
+/// Quantize positions to multiples of `quantization`, then snap them to the
+/// nearest pixel corner through `pixel_grid`, from positions to pixels and back.
+fn snap_positions(vertices: &mut [Vertex], quantization: Option<f32>, pixel_grid: Option<(Transform, Transform)>) {
+    if let Some(step) = quantization.filter(|step| *step > 0.0) {
+        for vertex in vertices.iter_mut() {
+            vertex.position = vertex.position.map(|value| (value / step).round() * step);
+        }
+    }
+    if let Some((to_pixels, from_pixels)) = pixel_grid {
+        for vertex in vertices {
+            let pixel = to_pixels.transform_point(Point::from(vertex.position)).round();
+            vertex.position = from_pixels.transform_point(pixel).to_array();
+        }
+    }
+}
+
+fn compute_normals(vertices: &mut [Vertex], indices: &[u16]) {
+    let normals = outline_normals(vertices, &boundary_edges(indices));
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal;
+    }
+}
+
+/// The edges used by exactly one triangle, with that triangle's third vertex.
+fn boundary_edges(indices: &[u16]) -> Vec<(u16, u16, u16)> {
+    let mut edges: HashMap<(u16, u16), (usize, u16)> = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for i in 0..3 {
+            let (a, b, c) = (triangle[i], triangle[(i+1)%3], triangle[(i+2)%3]);
+            edges.entry((a.min(b), a.max(b))).or_insert((0, c)).0 += 1;
+        }
+    }
+    edges.into_iter().filter(|(_, (count, _))| *count == 1).map(|((a, b), (_, c))| (a, b, c)).collect()
+}
+
+/// The normal of a boundary edge, scaled by its length, pointing away from
+/// the triangle's third vertex.
+fn edge_normal(vertices: &[Vertex], (a, b, c): (u16, u16, u16)) -> [f32; 2] {
+    let pa = vertices[a as usize].position;
+    let pb = vertices[b as usize].position;
+    let pc = vertices[c as usize].position;
+    let normal = [pb[1] - pa[1], pa[0] - pb[0]];
+    if normal[0] * (pc[0] - pa[0]) + normal[1] * (pc[1] - pa[1]) > 0.0 {
+        [-normal[0], -normal[1]]
+    } else {
+        normal
+    }
+}
+
+/// Unit normals pointing out of the outline at each boundary vertex, zero elsewhere.
+fn outline_normals(vertices: &[Vertex], edges: &[(u16, u16, u16)]) -> Vec<[f32; 2]> {
+    let mut normals = vec![[0.0f32; 2]; vertices.len()];
+    for &(a, b, c) in edges {
+        let normal = edge_normal(vertices, (a, b, c));
+        for v in [a, b] {
+            normals[v as usize][0] += normal[0];
+            normals[v as usize][1] += normal[1];
+        }
+    }
+
+    for normal in &mut normals {
+        let length = (normal[0] * normal[0] + normal[1] * normal[1]).sqrt();
+        *normal = if length > 0.0 {[normal[0] / length, normal[1] / length]} else {[0.0, 0.0]};
+    }
+    normals
+}
+
+/// A ring of `width` around the outline of the triangles in `indices`, with
+/// vertices pushed out from each boundary vertex along its outline normal.
+/// The push is lengthened at corners, up to four times the width, so edges
+/// keep the full width.
+#[cfg(feature = "wgpu")]
+fn outline_ring(vertices: &[Vertex], indices: &[u16], color: [f32; 3], width: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let edges = boundary_edges(indices);
+    let normals = outline_normals(vertices, &edges);
+    let mut fit = vec![1.0f32; vertices.len()];
+    for &edge in &edges {
+        let normal = edge_normal(vertices, edge);
+        let length = (normal[0] * normal[0] + normal[1] * normal[1]).sqrt();
+        if length == 0.0 {continue;}
+        for v in [edge.0, edge.1] {
+            let [x, y] = normals[v as usize];
+            fit[v as usize] = fit[v as usize].min((x * normal[0] + y * normal[1]) / length);
+        }
+    }
+
+    let mut ring_vertices = Vec::new();
+    let mut rings = HashMap::new();
+    let mut ring = |v: u16| *rings.entry(v).or_insert_with(|| {
+        let first = ring_vertices.len() as u32;
+        let vertex = vertices[v as usize];
+        let [x, y] = vertex.position;
+        let [nx, ny] = normals[v as usize].map(|value| value * width / fit[v as usize].max(0.25));
+        ring_vertices.push(Vertex::new([x, y], color, vertex.z));
+        ring_vertices.push(Vertex::new([x + nx, y + ny], color, vertex.z));
+        first
+    });
+    let mut ring_indices = Vec::with_capacity(edges.len() * 6);
+    for &(a, b, _) in &edges {
+        let (a, b) = (ring(a), ring(b));
+        ring_indices.extend([a, b, b + 1, a, b + 1, a + 1]);
     }
+    (ring_vertices, ring_indices)
+}
+
+/// Stroke the outline of the fill tessellated from `base_vertex` and `start`,
+/// appending the stroke after the fill, or replacing the fill unless
+/// `keep_fill`. The buffers are left as they were on error.
+fn stroke_outline(
+    buffers: &mut VertexBuffers<Vertex, u16>,
+    base_vertex: usize,
+    start: usize,
+    options: &StrokeOptions,
+    keep_fill: bool
+) -> Result<(), TessellationError> {
+    let vertices = &buffers.vertices[base_vertex..];
+    let edges = boundary_edges(&buffers.indices[start..]);
+    let mut neighbours: HashMap<u16, Vec<usize>> = HashMap::new();
+    for (i, &(a, b, _)) in edges.iter().enumerate() {
+        neighbours.entry(a).or_default().push(i);
+        neighbours.entry(b).or_default().push(i);
+    }
+
+    //Walk the boundary edges into contours, closing those that come back around
+    let attributes = |v: u16| {
+        let vertex = vertices[v as usize];
+        [vertex.color[0], vertex.color[1], vertex.color[2], vertex.z]
+    };
+    let position = |v: u16| Point::from(vertices[v as usize].position);
+    let mut used = vec![false; edges.len()];
+    let mut path = Path::builder_with_attributes(Vertex::ATTRIBUTE_COUNT);
+    for first in 0..edges.len() {
+        if used[first] {continue;}
+        used[first] = true;
+        let (origin, mut current, _) = edges[first];
+        path.begin(position(origin), &attributes(origin));
+        let closed = loop {
+            if current == origin {break true;}
+            path.line_to(position(current), &attributes(current));
+            let Some(&next) = neighbours[&current].iter().find(|edge| !used[**edge]) else {break false;};
+            used[next] = true;
+            let (a, b, _) = edges[next];
+            current = if a == current {b} else {a};
+        };
+        path.end(closed);
+    }
+
+    let mut stroke: VertexBuffers<Vertex, u16> = VertexBuffers::new();
+    StrokeTessellator::new().tessellate_path(&path.build(), options, &mut BuffersBuilder::new(
+        &mut stroke,
+        |mut vertex: StrokeVertex| {
+            let position = vertex.position().to_array();
+            match *vertex.interpolated_attributes() {
+                [r, g, b, z] => Vertex::new(position, [r, g, b], z),
+                _ => Vertex::new(position, [0.0, 0.0, 0.0], 0.0)
+            }
+        }
+    ))?;
+
+    let offset = if keep_fill {vertices.len()} else {0};
+    if offset + stroke.vertices.len() > u16::MAX as usize + 1 {
+        return Err(TessellationError::GeometryBuilder(GeometryBuilderError::TooManyVertices));
+    }
+    if !keep_fill {
+        buffers.vertices.truncate(base_vertex);
+        buffers.indices.truncate(start);
+    }
+    buffers.vertices.extend(stroke.vertices);
+    buffers.indices.extend(stroke.indices.into_iter().map(|index| index + offset as u16));
+    Ok(())
+}
+
+/// Append a shadow for the shape tessellated from `base_vertex` and `start`, with
+/// its indices, relative to `base_vertex` like the shape's, moved in front of
+/// the shape's so it is drawn first.
+///
+/// Returns the shape vertex, relative to `base_vertex`, each shadow vertex was made from.
+fn add_shadow(
+    buffers: &mut VertexBuffers<Vertex, u16>,
+    base_vertex: usize,
+    start: usize,
+    shadow: &Shadow
+) -> Result<Vec<usize>, TessellationError> {
+    const STEPS: usize = 4;
+    let vertices = &buffers.vertices[base_vertex..];
+    let indices = &buffers.indices[start..];
+    let edges = boundary_edges(indices);
+    let normals = outline_normals(vertices, &edges);
+
+    //Offset copy of the shape, then STEPS rings around each outline vertex
+    let mut shadow_vertices: Vec<Vertex> = vertices.iter().map(|vertex| Vertex{
+        position: [vertex.position[0] + shadow.offset.x, vertex.position[1] + shadow.offset.y],
+        color: shadow.color,
+        normal: [0.0, 0.0],
+        alpha: shadow.alpha,
+        ..*vertex
+    }).collect();
+    let mut origins: Vec<usize> = (0..shadow_vertices.len()).collect();
+    let mut rings = HashMap::new();
+    if shadow.blur_radius > 0.0 {
+        for &(a, b, _) in &edges {
+            for v in [a, b] {
+                rings.entry(v).or_insert_with(|| {
+                    let first = shadow_vertices.len();
+                    let core = shadow_vertices[v as usize];
+                    let normal = normals[v as usize];
+                    origins.extend([v as usize; STEPS]);
+                    for step in 1..=STEPS {
+                        let t = step as f32 / STEPS as f32;
+                        let distance = shadow.blur_radius * t;
+                        shadow_vertices.push(Vertex{
+                            position: [core.position[0] + normal[0] * distance, core.position[1] + normal[1] * distance],
+                            alpha: shadow.alpha * (1.0 - t * t * (3.0 - 2.0 * t)),
+                            ..core
+                        });
+                    }
+                    first
+                });
+            }
+        }
+    }
+
+    let shadow_base = buffers.vertices.len() - base_vertex;
+    if shadow_base + shadow_vertices.len() > u16::MAX as usize + 1 {
+        return Err(TessellationError::GeometryBuilder(GeometryBuilderError::TooManyVertices));
+    }
+    let ring = |v: u16, step: usize| if step == 0 {v as usize} else {rings[&v] + step - 1};
+    let mut shadow_indices: Vec<usize> = indices.iter().map(|index| *index as usize).collect();
+    if !rings.is_empty() {
+        for &(a, b, _) in &edges {
+            for step in 0..STEPS {
+                let (a0, b0, a1, b1) = (ring(a, step), ring(b, step), ring(a, step + 1), ring(b, step + 1));
+                shadow_indices.extend([a0, b0, b1, a0, b1, a1]);
+            }
+        }
+    }
+
+    let added = shadow_indices.len();
+    buffers.vertices.extend(shadow_vertices);
+    buffers.indices.extend(shadow_indices.into_iter().map(|index| (shadow_base + index) as u16));
+    buffers.indices[start..].rotate_right(added);
+    Ok(origins)
 }
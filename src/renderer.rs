@@ -0,0 +1,2670 @@
+use wgpu::{BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindGroupDescriptor, BindGroupEntry, BindingResource, BufferBinding, BufferBindingType, BindingType, ShaderStages, BindGroupLayout, BindGroup, BufferSize, PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, COPY_BUFFER_ALIGNMENT, RenderPassDepthStencilAttachment, RenderPassColorAttachment, RenderPassDescriptor, DepthStencilState, MultisampleState, BufferDescriptor, PipelineLayout, RenderPipeline, PrimitiveState, FragmentState, ShaderModule, TextureFormat, BufferUsages, IndexFormat, VertexState, TextureViewDescriptor, TextureView, TextureViewDimension, TextureDescriptor, TextureDimension, TextureUsages, Extent3d, CommandEncoderDescriptor, CompareFunction, LoadOp, StoreOp, StencilState, StencilFaceState, StencilOperation, DepthBiasState, BlendState, BlendComponent, BlendFactor, BlendOperation, ColorTargetState, ColorWrites, TextureFormatFeatureFlags, VertexBufferLayout, ErrorFilter, Features, ShaderModuleDescriptor, ShaderSource, Operations, Color, RenderBundle, RenderBundleEncoderDescriptor, RenderBundleDepthStencil, RenderBundleDescriptor, Texture, CommandEncoder, RenderPass, Buffer, Device, Queue};
+
+use lyon_tessellation::{FillOptions, FillTessellator};
+use lyon_tessellation::math::Transform;
+
+#[cfg(feature = "debug-attributes")]
+use lyon_tessellation::VertexSource;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::{AsViewport, Bound, GpuVertex, LyonError, Shape, Vertex, VertexLayout, TessellatedFrame, TessellationSettings, DrawCall, intersect_bounds, outline_ring};
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// A directional light applied using per-vertex normals derived from each
+/// shape's outline. Vertices away from the outline face straight up (+z).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    pub direction: [f32; 3],
+    pub ambient: f32
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting{direction: [-0.5, -0.5, 1.0], ambient: 0.3}
+    }
+}
+
+/// How vertex colors are blended across triangles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorInterpolation {
+    /// Blend the color values as given, matching older releases. Blending
+    /// sRGB colors this way darkens the middle of gradients.
+    #[default]
+    Srgb,
+    /// Treat colors as sRGB, blend them in linear space and encode the result
+    /// for the target format.
+    Linear
+}
+
+/// What `render_debug` draws in place of the shapes' colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMode {
+    /// Fill each shape with an opaque color hashed from its index, so shape
+    /// boundaries and overlaps stand out.
+    ShapeIndex
+}
+
+/// Whether colors are blended across each triangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shading {
+    /// Blend vertex colors across each triangle.
+    #[default]
+    Smooth,
+    /// Fill each triangle with the color of its first vertex. The vertex order
+    /// comes from the tessellator, not from the order points were added.
+    Flat
+}
+
+#[derive(Clone)]
+struct PipelineConfig {
+    texture_format: TextureFormat,
+    multisample: MultisampleState,
+    depth_stencil: Option<DepthStencilState>,
+    lighting: Option<Lighting>,
+    color_interpolation: ColorInterpolation,
+    shading: Shading,
+    aa_clip: bool,
+    clip_feather: f32,
+    color_writes: ColorWrites,
+    tinted: bool,
+    reverse_z: bool,
+    unclipped_depth: bool,
+    multiview: Option<NonZeroU32>,
+    vertex_layout: VertexLayout,
+    /// Whether `VertexLayout::Uniform` uploads normals, set when built with lighting.
+    uniform_normals: bool,
+    material_ids: bool,
+    /// The vertex and fragment entry points of a custom shader.
+    entry_points: Option<(String, String)>
+}
+
+impl PipelineConfig {
+    /// Single channel targets, like an `R8Unorm` glyph atlas, only receive
+    /// coverage. With multisampling the resolved edges hold partial coverage.
+    fn fragment_entry_point(&self) -> &str {
+        if let Some((_, fragment)) = &self.entry_points {
+            fragment
+        } else if self.texture_format.components() == 1 {
+            "fs_coverage"
+        } else if self.lighting.is_some() {
+            "fs_lit"
+        } else {
+            "fs_main"
+        }
+    }
+
+    /// `VertexLayout::Uniform` takes colors and z from the shape colors instead
+    /// of the vertices.
+    fn vertex_entry_point(&self) -> &str {
+        if let Some((vertex, _)) = &self.entry_points {
+            vertex
+        } else {
+            match self.gpu_vertex() {
+                GpuVertex::Uniform{normals: false} => "vs_uniform",
+                GpuVertex::Uniform{normals: true} => "vs_uniform_lit",
+                _ => "vs_main"
+            }
+        }
+    }
+
+    fn uniform_colors(&self) -> bool {self.vertex_layout == VertexLayout::Uniform}
+
+    fn gpu_vertex(&self) -> GpuVertex {
+        match self.vertex_layout {
+            VertexLayout::ColorZUv | VertexLayout::ColorZUvNormal => GpuVertex::Full,
+            VertexLayout::Uniform => GpuVertex::Uniform{normals: self.uniform_normals},
+            _ => GpuVertex::WithoutUv
+        }
+    }
+
+    fn vertex_buffer_layout(&self) -> VertexBufferLayout<'static> {Vertex::layout(self.gpu_vertex())}
+
+    fn picking_entry_point(&self) -> &str {
+        if self.uniform_colors() {"vs_picking_uniform"} else {"vs_picking"}
+    }
+
+    fn create_render_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        self.create_pipeline(
+            device, shader, pipeline_layout, self.vertex_entry_point(), Some(self.fragment_entry_point()),
+            &[self.vertex_buffer_layout()], self.depth_stencil.clone()
+        )
+    }
+
+    /// The grid covers the whole target behind everything else, so it leaves
+    /// depth alone and only keeps the stencil test.
+    fn create_grid_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let depth_stencil = self.depth_stencil.clone().map(|depth_stencil| DepthStencilState{
+            depth_write_enabled: false,
+            depth_compare: CompareFunction::Always,
+            ..depth_stencil
+        });
+        self.create_pipeline(device, shader, pipeline_layout, "vs_grid", Some("fs_grid"), &[], depth_stencil)
+    }
+
+    /// Multiplies the color by the pass's blend constant, see `render_tinted`.
+    fn create_tinted_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        //The tinted module is always the crate's shader
+        PipelineConfig{tinted: true, entry_points: None, ..self.clone()}.create_render_pipeline(device, shader, pipeline_layout)
+    }
+
+    /// Tests depth without writing it, so transparent shapes drawn by
+    /// `render_sorted` do not hide each other.
+    fn create_transparent_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let depth_stencil = self.depth_stencil.clone().map(|depth_stencil| DepthStencilState{
+            depth_write_enabled: false,
+            ..depth_stencil
+        });
+        self.create_pipeline(
+            device, shader, pipeline_layout, self.vertex_entry_point(), Some(self.fragment_entry_point()),
+            &[self.vertex_buffer_layout()], depth_stencil
+        )
+    }
+
+    /// Writes the pass's stencil reference wherever a clip mask is drawn, without
+    /// touching color or depth, to set a mask before its shape and clear it after.
+    fn create_mask_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let format = self.depth_stencil.as_ref().expect("Clip masks need a depth stencil state").format;
+        let config = PipelineConfig{color_writes: ColorWrites::empty(), ..self.clone()};
+        config.create_pipeline(
+            device, shader, pipeline_layout, self.vertex_entry_point(), Some(self.fragment_entry_point()),
+            &[self.vertex_buffer_layout()], Some(depth_stencil_mask(format))
+        )
+    }
+
+    /// The render pipeline, only drawing where the stencil matches the pass's
+    /// stencil reference, for shapes inside their clip mask.
+    fn create_masked_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let depth_stencil = self.depth_stencil.clone().map(|depth_stencil| DepthStencilState{
+            stencil: depth_stencil_clip(depth_stencil.format).stencil,
+            ..depth_stencil
+        });
+        self.create_pipeline(
+            device, shader, pipeline_layout, self.vertex_entry_point(), Some(self.fragment_entry_point()),
+            &[self.vertex_buffer_layout()], depth_stencil
+        )
+    }
+
+    /// Only writes depth, without a fragment stage, for passes with a depth
+    /// attachment alone. Single sampled like picking.
+    fn create_depth_only_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let config = PipelineConfig{multisample: MultisampleState::default(), ..self.clone()};
+        config.create_pipeline(
+            device, shader, pipeline_layout, self.vertex_entry_point(), None,
+            &[self.vertex_buffer_layout()], self.depth_stencil.clone()
+        )
+    }
+
+    /// Writes shape indices to an `R32Uint` target without multisampling or
+    /// blending, keeping the depth test so overlaps resolve as when rendering.
+    fn create_picking_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let config = PipelineConfig{
+            texture_format: TextureFormat::R32Uint,
+            multisample: MultisampleState::default(),
+            aa_clip: false,
+            ..self.clone()
+        };
+        config.create_pipeline(
+            device, shader, pipeline_layout, self.picking_entry_point(), Some("fs_picking"),
+            &[self.vertex_buffer_layout()], config.depth_stencil.clone()
+        )
+    }
+
+    /// Replaces the fragment output with a color per shape, keeping the target
+    /// format, blending and depth test of the render pipeline.
+    fn create_debug_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        self.create_pipeline(
+            device, shader, pipeline_layout, self.picking_entry_point(), Some("fs_shape_index"),
+            &[self.vertex_buffer_layout()], self.depth_stencil.clone()
+        )
+    }
+
+    /// Renders both views of a two layer target in one pass, taking each
+    /// view's camera from the stereo cameras. `shader` must include stereo.wgsl.
+    fn create_stereo_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let config = PipelineConfig{multiview: NonZeroU32::new(2), entry_points: None, ..self.clone()};
+        let vertex_entry_point = match self.gpu_vertex() {
+            GpuVertex::Uniform{normals: false} => "vs_stereo_uniform",
+            GpuVertex::Uniform{normals: true} => "vs_stereo_uniform_lit",
+            _ => "vs_stereo"
+        };
+        config.create_pipeline(
+            device, shader, pipeline_layout, vertex_entry_point, Some(config.fragment_entry_point()),
+            &[self.vertex_buffer_layout()], self.depth_stencil.clone()
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipeline(
+        &self,
+        device: &Device,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+        vertex_entry_point: &str,
+        fragment_entry_point: Option<&str>,
+        buffers: &[VertexBufferLayout<'_>],
+        depth_stencil: Option<DepthStencilState>
+    ) -> RenderPipeline {
+        let mut constants = HashMap::new();
+        if let Some(lighting) = self.lighting {
+            constants.insert("light_x".to_string(), lighting.direction[0] as f64);
+            constants.insert("light_y".to_string(), lighting.direction[1] as f64);
+            constants.insert("light_z".to_string(), lighting.direction[2] as f64);
+            constants.insert("ambient".to_string(), lighting.ambient as f64);
+        }
+        if self.color_interpolation == ColorInterpolation::Linear {
+            //sRGB targets encode the linear output themselves
+            let encode = !self.texture_format.is_srgb();
+            constants.insert("decode_srgb".to_string(), 1.0);
+            constants.insert("encode_srgb".to_string(), if encode {1.0} else {0.0});
+        }
+        if self.shading == Shading::Flat {
+            constants.insert("flat_shading".to_string(), 1.0);
+        }
+        if self.aa_clip {
+            constants.insert("aa_clip".to_string(), 1.0);
+            //Left to the shader's default so custom shaders need not declare it
+            if self.clip_feather != 1.0 {
+                constants.insert("clip_feather".to_string(), self.clip_feather as f64);
+            }
+        }
+        if self.tinted {
+            constants.insert("premultiply_alpha".to_string(), 1.0);
+        }
+        if self.reverse_z {
+            constants.insert("reverse_z".to_string(), 1.0);
+        }
+        if self.material_ids {
+            constants.insert("material_ids".to_string(), 1.0);
+        }
+        //Coverage targets take the coverage as is, color targets blend by it and by
+        //shadow alpha. Opaque fragments blend to the same result as replacing.
+        let blendable = self.texture_format.guaranteed_format_features(device.features())
+            .flags.contains(TextureFormatFeatureFlags::BLENDABLE);
+        //Tinting scales the premultiplied color and the coverage by the blend constant instead
+        let tinted = BlendState{
+            color: BlendComponent{
+                src_factor: BlendFactor::Constant,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendComponent{
+                src_factor: BlendFactor::Constant,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+        };
+        //Material ids in alpha have to replace what is there
+        let blend = (blendable && self.texture_format.components() > 1 && !self.material_ids)
+            .then_some(if self.tinted {tinted} else {BlendState::ALPHA_BLENDING});
+        let targets = [Some(ColorTargetState{
+            format: self.texture_format,
+            blend,
+            write_mask: self.color_writes
+        })];
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(pipeline_layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: vertex_entry_point,
+                compilation_options: PipelineCompilationOptions{
+                    constants: &constants,
+                    ..Default::default()
+                },
+                buffers
+            },
+            fragment: fragment_entry_point.map(|entry_point| FragmentState {
+                module: shader,
+                entry_point,
+                compilation_options: PipelineCompilationOptions{
+                    constants: &constants,
+                    ..Default::default()
+                },
+                targets: &targets,
+            }),
+            //Triangle lists only, see `LyonRenderer::render`
+            primitive: PrimitiveState{unclipped_depth: self.unclipped_depth, ..PrimitiveState::default()},
+            depth_stencil,
+            multisample: self.multisample,
+            multiview: self.multiview,
+            cache: None
+        })
+    }
+}
+
+/// Depth testing for 2D z-ordering: lower z draws over higher z, and shapes at
+/// the same z draw in submission order. `format` must match the depth attachment.
+pub fn depth_only(format: TextureFormat) -> DepthStencilState {
+    assert!(format.has_depth_aspect(), "{:?} has no depth aspect", format);
+    DepthStencilState{
+        format,
+        depth_write_enabled: true,
+        depth_compare: CompareFunction::LessEqual,
+        stencil: StencilState::default(),
+        bias: DepthBiasState::default(),
+    }
+}
+
+/// `depth_only` plus a stencil test that only draws where the stencil matches
+/// the pass's `set_stencil_reference`, leaving the stencil untouched. The clip
+/// is written into the stencil attachment by the caller beforehand, e.g. by a
+/// renderer using `depth_stencil_mask`.
+pub fn depth_stencil_clip(format: TextureFormat) -> DepthStencilState {
+    assert!(format.has_stencil_aspect(), "{:?} has no stencil aspect", format);
+    let face = StencilFaceState{
+        compare: CompareFunction::Equal,
+        fail_op: StencilOperation::Keep,
+        depth_fail_op: StencilOperation::Keep,
+        pass_op: StencilOperation::Keep,
+    };
+    DepthStencilState{
+        stencil: StencilState{front: face, back: face, read_mask: 0xff, write_mask: 0},
+        ..depth_only(format)
+    }
+}
+
+/// Like `depth_stencil_clip`, but only draws where the stencil does not match
+/// the reference, e.g. outside a mask from `depth_stencil_mask`.
+pub fn depth_stencil_inverse_clip(format: TextureFormat) -> DepthStencilState {
+    let mut depth_stencil = depth_stencil_clip(format);
+    depth_stencil.stencil.front.compare = CompareFunction::NotEqual;
+    depth_stencil.stencil.back.compare = CompareFunction::NotEqual;
+    depth_stencil
+}
+
+/// Writes the pass's `set_stencil_reference` wherever a shape is drawn, for
+/// the clips of `depth_stencil_clip` and `depth_stencil_inverse_clip`. Depth is
+/// neither tested nor written, so masks do not hide later shapes by z.
+///
+/// The stencil keeps the masks until it is cleared, which `encode_with_depth`
+/// does at the start of each pass.
+pub fn depth_stencil_mask(format: TextureFormat) -> DepthStencilState {
+    assert!(format.has_stencil_aspect(), "{:?} has no stencil aspect", format);
+    let face = StencilFaceState{
+        compare: CompareFunction::Always,
+        fail_op: StencilOperation::Keep,
+        depth_fail_op: StencilOperation::Keep,
+        pass_op: StencilOperation::Replace,
+    };
+    DepthStencilState{
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: StencilState{front: face, back: face, read_mask: 0xff, write_mask: 0xff},
+        ..depth_only(format)
+    }
+}
+
+/// Grid lines drawn by `LyonRenderer::render_grid` across the whole target.
+///
+/// Lines are anti-aliased over one pixel and keep their thickness when the
+/// camera zooms. Lines thinner than a pixel fade instead of disappearing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    /// Distance between minor lines in world units.
+    pub spacing: f32,
+    /// Line thickness in pixels.
+    pub thickness: f32,
+    /// RGBA, blended over what is already in the target.
+    pub color: [f32; 4],
+    /// Draw every nth line in `major_color`, or no major lines when 0.
+    pub major_every: u32,
+    pub major_color: [f32; 4]
+}
+
+impl Grid {
+    fn uniform(&self) -> [f32; 12] {
+        let [r, g, b, a] = self.color;
+        let [major_r, major_g, major_b, major_a] = self.major_color;
+        [
+            r, g, b, a,
+            major_r, major_g, major_b, major_a,
+            self.spacing, self.thickness, self.major_every as f32, 0.0
+        ]
+    }
+}
+
+/// A ring around a prepared shape drawn by `LyonRenderer::render_outline`,
+/// e.g. a focus indicator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    /// The index of the shape in each `prepare`.
+    pub shape: usize,
+    pub color: [f32; 3],
+    /// Width outward from the shape's edges, in the units of its tessellated
+    /// vertices.
+    pub width: f32
+}
+
+/// What a render pass drawn into by `LyonRenderer::render` has to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassRequirements {
+    pub color_format: TextureFormat,
+    pub sample_count: u32,
+    /// The format of the depth attachment, if the renderer has a depth stencil state.
+    pub depth_format: Option<TextureFormat>,
+    /// Whether the stencil is tested or written, needing a stencil aspect.
+    pub stencil: bool
+}
+
+/// A run of consecutive prepared shapes from `LyonRenderer::split_into_groups`,
+/// drawn like `render` with the renderer's shared buffers.
+#[derive(Clone)]
+pub struct RenderGroup<'a> {
+    renderer: &'a LyonRenderer,
+    shapes: Range<usize>
+}
+
+impl RenderGroup<'_> {
+    /// The indices of the shapes in the group.
+    pub fn shapes(&self) -> Range<usize> {self.shapes.clone()}
+
+    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
+        let renderer = self.renderer;
+        if !renderer.bind_frame(render_pass, &renderer.render_pipeline, 0) {return;}
+        for i in self.shapes.clone() {
+            renderer.draw_shape(render_pass, i, 0, None, None, false, true);
+        }
+        renderer.reset_scissor(render_pass);
+    }
+}
+
+/// The shapes of the last `prepare`, kept to tessellate them again as the
+/// camera zooms.
+struct RetainedShapes {
+    shapes: Vec<Shape>,
+    fill_options: FillOptions,
+    clips: Vec<Bound>,
+    /// The camera scale the shapes were last tessellated at.
+    scale: f32
+}
+
+/// Tessellator state reused across `LyonRenderer::prepare_with_scratch` calls.
+#[derive(Default)]
+pub struct PrepareScratch {
+    tessellator: FillTessellator
+}
+
+pub struct LyonRendererBuilder {
+    multisample: MultisampleState,
+    depth_stencil: Option<DepthStencilState>,
+    depth_compare: Option<CompareFunction>,
+    depth_write_enabled: Option<bool>,
+    reverse_z: bool,
+    unclipped_depth: bool,
+    lighting: Option<Lighting>,
+    color_interpolation: ColorInterpolation,
+    shading: Shading,
+    aa_clip: bool,
+    clip_feather: f32,
+    color_writes: ColorWrites,
+    bind_when_empty: bool,
+    max_buffer_bytes: u64,
+    max_shapes: usize,
+    combined_buffer: bool,
+    keep_cpu_geometry: bool,
+    sanitize: bool,
+    coordinate_transform: Option<[[f32; 3]; 3]>,
+    position_quantization: Option<f32>,
+    picking: bool,
+    tinting: bool,
+    depth_only: bool,
+    material_ids: bool,
+    clip_masks: bool,
+    sorted: bool,
+    debug: bool,
+    stereo: bool,
+    tessellation_threads: usize,
+    vertex_layout: VertexLayout,
+    fill_options: FillOptions,
+    auto_retessellate: bool,
+    shader: Option<Cow<'static, str>>,
+    entry_points: Option<(String, String)>,
+}
+
+impl Default for LyonRendererBuilder {
+    fn default() -> Self {
+        LyonRendererBuilder{
+            multisample: MultisampleState::default(),
+            depth_stencil: None,
+            depth_compare: None,
+            depth_write_enabled: None,
+            reverse_z: false,
+            unclipped_depth: false,
+            lighting: None,
+            color_interpolation: ColorInterpolation::default(),
+            shading: Shading::default(),
+            aa_clip: false,
+            clip_feather: 1.0,
+            color_writes: ColorWrites::ALL,
+            bind_when_empty: false,
+            max_buffer_bytes: u64::MAX,
+            max_shapes: usize::MAX,
+            combined_buffer: false,
+            keep_cpu_geometry: true,
+            sanitize: false,
+            coordinate_transform: None,
+            position_quantization: None,
+            picking: false,
+            tinting: false,
+            depth_only: false,
+            material_ids: false,
+            clip_masks: false,
+            sorted: false,
+            debug: false,
+            stereo: false,
+            tessellation_threads: 1,
+            vertex_layout: VertexLayout::ColorZ,
+            fill_options: FillOptions::default(),
+            auto_retessellate: false,
+            shader: None,
+            entry_points: None
+        }
+    }
+}
+
+impl LyonRendererBuilder {
+    pub fn multisample(mut self, multisample: MultisampleState) -> Self {
+        self.multisample = multisample;
+        self
+    }
+
+    pub fn depth_stencil(mut self, depth_stencil: Option<DepthStencilState>) -> Self {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    /// Override the depth compare function of the `depth_stencil` state, e.g.
+    /// `LessEqual` so coplanar overlays draw over an equal-depth base.
+    ///
+    /// Reverse-z (`Greater` or `GreaterEqual`) needs the depth attachment
+    /// cleared to `0.0` instead of the usual `1.0`, or nothing will pass. See
+    /// `reverse_z` to also flip the depth of each z.
+    /// Has no effect without a `depth_stencil` state.
+    pub fn depth_compare(mut self, depth_compare: CompareFunction) -> Self {
+        self.depth_compare = Some(depth_compare);
+        self
+    }
+
+    /// Override whether the `depth_stencil` state writes depth. Has no effect
+    /// without a `depth_stencil` state.
+    pub fn depth_write_enabled(mut self, depth_write_enabled: bool) -> Self {
+        self.depth_write_enabled = Some(depth_write_enabled);
+        self
+    }
+
+    /// Map z 0 to depth 1 and z 1 to depth 0 for better precision with float
+    /// depth formats. `Less` and `LessEqual` compares of the `depth_stencil`
+    /// state become `Greater` and `GreaterEqual` unless set by `depth_compare`,
+    /// so lower z still draws over higher z. The depth attachment has to be
+    /// cleared to `LyonRenderer::clear_depth`, and depth written without
+    /// reverse-z, e.g. by another renderer sharing the attachment, does not
+    /// compare correctly against it.
+    pub fn reverse_z(mut self, reverse_z: bool) -> Self {
+        self.reverse_z = reverse_z;
+        self
+    }
+
+    /// Clamp the depth of fragments to the viewport's depth range instead of
+    /// clipping geometry outside it, so shapes with z past the near or far
+    /// plane still draw there, e.g. for extreme layering. Shapes clamped to the
+    /// same plane then compare equal. Requires `Features::DEPTH_CLIP_CONTROL`.
+    /// Off by default.
+    pub fn unclipped_depth(mut self, unclipped_depth: bool) -> Self {
+        self.unclipped_depth = unclipped_depth;
+        self
+    }
+
+    pub fn lighting(mut self, lighting: Option<Lighting>) -> Self {
+        self.lighting = lighting;
+        self
+    }
+
+    pub fn color_interpolation(mut self, color_interpolation: ColorInterpolation) -> Self {
+        self.color_interpolation = color_interpolation;
+        self
+    }
+
+    pub fn shading(mut self, shading: Shading) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    /// The color channels the pipelines write, all by default. Masks drawn with
+    /// `depth_stencil_mask` use `ColorWrites::empty()` to only touch the stencil.
+    pub fn color_writes(mut self, color_writes: ColorWrites) -> Self {
+        self.color_writes = color_writes;
+        self
+    }
+
+    /// Anti-alias the edges of the clip from `set_aa_clip` by fading coverage
+    /// over one pixel, or over `clip_feather` pixels. Off by default, where
+    /// the clip is a hard scissor rounded to the nearest pixel.
+    pub fn aa_clip(mut self, aa_clip: bool) -> Self {
+        self.aa_clip = aa_clip;
+        self
+    }
+
+    /// Fade the clip from `set_aa_clip` out over `feather` pixels centered on
+    /// its edges instead of one, e.g. for soft edged panels, turning on
+    /// `aa_clip`. A feather wider than the clip is clamped to its size.
+    ///
+    /// Panics if `feather` is not positive and finite.
+    pub fn clip_feather(mut self, feather: f32) -> Self {
+        assert!(feather > 0.0 && feather.is_finite(), "Clip feather must be positive, got {}", feather);
+        self.aa_clip = true;
+        self.clip_feather = feather;
+        self
+    }
+
+    /// Make `render` bind this renderer's state even when there is nothing to
+    /// draw, resetting the scissor to the size given to `resize`, so chained
+    /// renderers always leave the pass in the same state. Off by default.
+    pub fn bind_when_empty(mut self, bind_when_empty: bool) -> Self {
+        self.bind_when_empty = bind_when_empty;
+        self
+    }
+
+    /// Make `prepare` return an error instead of allocating a vertex or index
+    /// buffer larger than this. Unlimited by default.
+    pub fn max_buffer_bytes(mut self, max_buffer_bytes: u64) -> Self {
+        self.max_buffer_bytes = max_buffer_bytes;
+        self
+    }
+
+    /// Make `prepare` tessellate at most this many shapes, keeping the first
+    /// ones and dropping the rest, e.g. for shapes from an untrusted source.
+    /// `truncated_shapes` reports how many were dropped. Unlimited by default.
+    pub fn max_shapes(mut self, max_shapes: usize) -> Self {
+        self.max_shapes = max_shapes;
+        self
+    }
+
+    /// Upload vertices and indices into one buffer with a single write per
+    /// `prepare`, the indices following the vertices at a copy aligned offset.
+    /// `buffer_sizes` then reports the combined buffer as the vertex buffer
+    /// and no index buffer.
+    /// Off by default.
+    ///
+    /// Requires `DownlevelFlags::UNRESTRICTED_INDEX_BUFFER`, which WebGL lacks.
+    pub fn combined_buffer(mut self, combined_buffer: bool) -> Self {
+        self.combined_buffer = combined_buffer;
+        self
+    }
+
+    /// Keep the tessellated vertices and indices on the CPU after uploading
+    /// them. On by default. Without them a large static scene is not held in
+    /// memory twice, but `triangles` is empty, `vertex_sources` too, and
+    /// `set_outline` finds no fill to outline until the next `prepare`.
+    pub fn keep_cpu_geometry(mut self, keep_cpu_geometry: bool) -> Self {
+        self.keep_cpu_geometry = keep_cpu_geometry;
+        self
+    }
+
+    /// Make `prepare` skip shapes that fail to tessellate or produce NaN or
+    /// infinite vertices, e.g. from a division by zero in layout, instead of
+    /// failing or uploading them. Skipped shapes are reported by `skipped_shapes`.
+    /// Off by default.
+    ///
+    /// lyon may panic on NaN input in debug builds before it can be skipped.
+    pub fn sanitize(mut self, sanitize: bool) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// Bake a column major 2D homogeneous transform into every vertex position
+    /// on the CPU, before positions are quantized. It stacks with the camera,
+    /// which is applied after it on the GPU. Shadows are added to the
+    /// transformed shape, so their offset and blur are in transformed units.
+    pub fn coordinate_transform(mut self, transform: Option<[[f32; 3]; 3]>) -> Self {
+        self.coordinate_transform = transform;
+        self
+    }
+
+    /// Snap every vertex position to a multiple of this step after tessellation,
+    /// so floating point differences between platforms do not move pixels,
+    /// e.g. for lockstep networking or golden images. `None` or a step of zero,
+    /// the default, keeps positions as tessellated.
+    pub fn position_quantization(mut self, step: Option<f32>) -> Self {
+        self.position_quantization = step;
+        self
+    }
+
+    /// Build the pipeline used by `render_picking`. Off by default.
+    pub fn picking(mut self, picking: bool) -> Self {
+        self.picking = picking;
+        self
+    }
+
+    /// Build the pipeline used by `render_tinted`. Off by default.
+    pub fn tinting(mut self, tinting: bool) -> Self {
+        self.tinting = tinting;
+        self
+    }
+
+    /// Build the pipeline used by `render_depth_only`, which needs a
+    /// `depth_stencil` state. Off by default.
+    pub fn depth_only(mut self, depth_only: bool) -> Self {
+        self.depth_only = depth_only;
+        self
+    }
+
+    /// Write the vertex alpha, which holds the material id of shapes from
+    /// `Shape::with_material_id`, as the output alpha without blending, e.g.
+    /// for a G-buffer. Shapes without an id write their alpha, normally 1, and
+    /// anti-aliased clip edges are not faded. Use a single sampled target, as
+    /// resolving would average ids along shape edges. Off by default.
+    pub fn material_ids(mut self, material_ids: bool) -> Self {
+        self.material_ids = material_ids;
+        self
+    }
+
+    /// Build the pipelines drawing shapes from `Shape::with_clip_mask`, which
+    /// need a `depth_stencil` state with a depth and stencil format, e.g.
+    /// `Depth24PlusStencil8`. Without them masks are ignored. Off by default.
+    ///
+    /// Each masked shape draws its mask into the stencil, then itself where the
+    /// stencil is set, then clears the mask, so masks replace any stencil
+    /// values under them with 0 and leave the pass's stencil reference at 0.
+    /// Only `render`, `render_with_camera`, `render_with_transforms`,
+    /// `render_clipped`, `render_dirty` and render groups apply masks.
+    pub fn clip_masks(mut self, clip_masks: bool) -> Self {
+        self.clip_masks = clip_masks;
+        self
+    }
+
+    /// Build the depth-test-only pipeline `render_sorted` draws transparent
+    /// shapes with. Off by default.
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Build the pipeline used by `render_debug`. Off by default.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Build the multiview pipeline used by `render_stereo`. Requires
+    /// `Features::MULTIVIEW`. Off by default.
+    pub fn stereo(mut self, stereo: bool) -> Self {
+        self.stereo = stereo;
+        self
+    }
+
+    /// Split tessellation across this many scoped threads, e.g. to leave cores
+    /// to an engine's own pool. 0 uses every core and 1, the default, keeps
+    /// tessellation on the calling thread. The output does not depend on it.
+    pub fn tessellation_threads(mut self, threads: usize) -> Self {
+        self.tessellation_threads = threads;
+        self
+    }
+
+    /// The fill options used by `prepare_default`. Defaults to `FillOptions::default()`.
+    pub fn fill_options(mut self, fill_options: FillOptions) -> Self {
+        self.fill_options = fill_options;
+        self
+    }
+
+    /// Keep the shapes of each `prepare` and tessellate them again from
+    /// `set_cameras` once the first camera zooms by more than
+    /// `LyonRenderer::RETESSELLATE_RATIO`, scaling the tolerance so curves
+    /// stay smooth. The tolerance of the fill options applies at a camera
+    /// scale of 1. See `LyonRenderer::set_cameras`.
+    pub fn auto_retessellate_on_scale(mut self, auto_retessellate: bool) -> Self {
+        self.auto_retessellate = auto_retessellate;
+        self
+    }
+
+    /// The builder attributes every prepared shape provides. Defaults to
+    /// `VertexLayout::ColorZ`.
+    pub fn vertex_layout(mut self, vertex_layout: VertexLayout) -> Self {
+        self.vertex_layout = vertex_layout;
+        self
+    }
+
+    /// Replace the crate's shader with WGSL of the caller's, keeping the
+    /// vertex layout and group 0 bindings of `camera_layout_entries`. Override
+    /// constants set by the builder's options have to be declared. Pipelines
+    /// built by other options take their usual entry points, like `vs_grid`,
+    /// from it, except for tinting and stereo, which keep the crate's shader.
+    /// Use `try_build` to get missing entry points as an error.
+    pub fn shader(mut self, wgsl: impl Into<Cow<'static, str>>) -> Self {
+        self.shader = Some(wgsl.into());
+        self
+    }
+
+    /// The entry points of the render pipeline, for a custom `shader` that
+    /// does not name them `vs_main` and `fs_main`. They replace the entry
+    /// points the builder would pick, e.g. the `fs_lit` of lighting.
+    pub fn entry_points(mut self, vertex: &str, fragment: &str) -> Self {
+        self.entry_points = Some((vertex.to_string(), fragment.to_string()));
+        self
+    }
+
+    pub fn build(self, device: &Device, texture_format: &TextureFormat) -> LyonRenderer {
+        LyonRenderer::from_builder(self, device, texture_format, None)
+    }
+
+    /// Like `build`, but returns wgpu's validation error, e.g. a custom shader
+    /// that fails to compile or lacks an entry point, instead of panicking.
+    /// Options needing features the device lacks return `MissingFeature`.
+    pub fn try_build(self, device: &Device, texture_format: &TextureFormat) -> Result<LyonRenderer, LyonError> {
+        self.check_features(device)?;
+        validated(device, || LyonRenderer::from_builder(self, device, texture_format, None))
+            .map_err(|message| LyonError::Shader{message})
+    }
+
+    /// Build with the caller's pipeline layout instead of the renderer's own,
+    /// for engines that manage every bind group themselves. See
+    /// `LyonRenderer::new_with_layout`.
+    pub fn build_with_layout(
+        self,
+        device: &Device,
+        texture_format: &TextureFormat,
+        pipeline_layout: PipelineLayout
+    ) -> Result<LyonRenderer, LyonError> {
+        self.check_features(device)?;
+        validated(device, || LyonRenderer::from_builder(self, device, texture_format, Some(pipeline_layout)))
+            .map_err(|message| LyonError::PipelineLayout{message})
+    }
+
+    fn check_features(&self, device: &Device) -> Result<(), LyonError> {
+        let mut features = Features::empty();
+        features.set(Features::MULTIVIEW, self.stereo);
+        features.set(Features::DEPTH_CLIP_CONTROL, self.unclipped_depth);
+        let missing = features - device.features();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(LyonError::MissingFeature{features: missing})
+        }
+    }
+}
+
+/// Run `build`, catching the first validation error it raises.
+fn validated<T>(device: &Device, build: impl FnOnce() -> T) -> Result<T, String> {
+    //Error scopes resolve asynchronously on the web, where the check would
+    //swallow the error instead of reporting it
+    let check = !cfg!(target_arch = "wasm32");
+    if check {
+        device.push_error_scope(ErrorFilter::Validation);
+    }
+    let built = build();
+    if check {
+        let mut error = std::pin::pin!(device.pop_error_scope());
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        if let std::task::Poll::Ready(Some(error)) = std::future::Future::poll(error.as_mut(), &mut context) {
+            return Err(error.to_string());
+        }
+    }
+    Ok(built)
+}
+
+pub struct LyonRenderer {
+    shader: ShaderModule,
+    shader_source: Option<Cow<'static, str>>,
+    pipeline_layout: PipelineLayout,
+    custom_layout: bool,
+    config: PipelineConfig,
+    render_pipeline: RenderPipeline,
+    camera_layout: BindGroupLayout,
+    camera_stride: u64,
+    camera_buffer_size: u64,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    /// The shape transforms of the frame, one per camera stride, bound in place
+    /// of the cameras. Created by the first frame with transforms.
+    transform_buffer: Option<(Buffer, BindGroup)>,
+    clip_buffer: Buffer,
+    clip_scissor: Option<Bound>,
+    grid_buffer: Buffer,
+    grid_pipeline: Option<RenderPipeline>,
+    outline: Option<Outline>,
+    /// The outline's vertices and `u32` indices, with the index count.
+    outline_buffers: Option<(Buffer, Buffer, u32)>,
+    picking_pipeline: Option<RenderPipeline>,
+    tinted: Option<(ShaderModule, RenderPipeline)>,
+    transparent_pipeline: Option<RenderPipeline>,
+    depth_only_pipeline: Option<RenderPipeline>,
+    /// The pipelines writing clip masks into the stencil and drawing inside them.
+    mask_pipelines: Option<(RenderPipeline, RenderPipeline)>,
+    debug_pipeline: Option<RenderPipeline>,
+    stereo_buffer: Buffer,
+    stereo: Option<(ShaderModule, RenderPipeline)>,
+    shape_color_buffer: Buffer,
+    user_bind_groups: Vec<(u32, Arc<BindGroup>)>,
+    empty_bind_groups: Vec<(u32, BindGroup)>,
+    vertex_buffer_size: u64,
+    vertex_buffer: Buffer,
+    /// The uploaded bytes of the frame's vertices, for layouts that leave fields out.
+    packed_vertices: Vec<u8>,
+    index_buffer_size: u64,
+    index_buffer: Buffer,
+    combined_buffer: bool,
+    index_offset: u64,
+    frame: TessellatedFrame,
+    keep_cpu_geometry: bool,
+    /// Whether the frame's geometry was uploaded and then freed.
+    geometry_freed: bool,
+    clips: Vec<Bound>,
+    reallocated: bool,
+    uploaded_bytes: u64,
+    target_size: Option<(u32, u32)>,
+    bind_when_empty: bool,
+    max_buffer_bytes: u64,
+    max_shapes: usize,
+    truncated: usize,
+    sanitize: bool,
+    coordinate_transform: Option<[[f32; 3]; 3]>,
+    position_quantization: Option<f32>,
+    tessellation_threads: usize,
+    vertex_layout: VertexLayout,
+    fill_options: FillOptions,
+    auto_retessellate: bool,
+    camera_scale: f32,
+    /// The first camera from `set_cameras`, for snapping shapes to pixels.
+    camera: [[f32; 4]; 4],
+    retained: Option<RetainedShapes>
+}
+
+impl LyonRenderer {
+    const CAMERA_SIZE: u64 = std::mem::size_of::<[[f32; 4]; 4]>() as u64;
+    const NO_CLIP: [f32; 4] = [-f32::MAX, -f32::MAX, f32::MAX, f32::MAX];
+
+    /// How far the camera scale has to move from the scale shapes were last
+    /// tessellated at, up or down, before `auto_retessellate_on_scale`
+    /// tessellates them again. Measuring from the last tessellation keeps
+    /// zooming back and forth around one scale from retessellating every frame.
+    pub const RETESSELLATE_RATIO: f32 = 2.0;
+
+    /// The most shapes a frame can hold with `VertexLayout::Uniform`.
+    pub const MAX_UNIFORM_SHAPES: usize = 1024;
+
+    /// Bind group indices used by the crate's own uniforms.
+    pub const RESERVED_BIND_GROUPS: &'static [u32] = &[0];
+
+    /// Create all unchanging resources here.
+    pub fn new(
+        device: &Device,
+        texture_format: &TextureFormat,
+        multisample: MultisampleState,
+        depth_stencil: Option<DepthStencilState>,
+    ) -> Self {
+        Self::builder()
+            .multisample(multisample)
+            .depth_stencil(depth_stencil)
+            .build(device, texture_format)
+    }
+
+    /// A renderer without multisampling or depth, for the common case.
+    pub fn with_defaults(device: &Device, texture_format: &TextureFormat) -> Self {
+        Self::builder().build(device, texture_format)
+    }
+
+    pub fn builder() -> LyonRendererBuilder {LyonRendererBuilder::default()}
+
+    /// Request an adapter and a device without a surface and build a renderer
+    /// for `Rgba8Unorm` targets with them, e.g. for browser demos and tests.
+    /// The device only asks for WebGL2's downlevel limits, so the same setup
+    /// works on WebGPU and WebGL2; the latter needs wgpu's `webgl` feature.
+    #[cfg(feature = "headless")]
+    pub async fn headless_async() -> Result<(Self, Device, Queue), LyonError> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await
+            .ok_or(LyonError::NoAdapter)?;
+        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: Features::empty(),
+            required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            memory_hints: wgpu::MemoryHints::default(),
+        }, None).await.map_err(|error| LyonError::RequestDevice{message: error.to_string()})?;
+        let renderer = Self::with_defaults(&device, &TextureFormat::Rgba8Unorm);
+        Ok((renderer, device, queue))
+    }
+
+    /// Create a renderer whose pipelines use the caller's layout. Its group 0
+    /// must be compatible with `camera_layout_entries`, and any other groups
+    /// are up to the caller.
+    ///
+    /// The renderer then only binds its pipeline: the caller sets every bind
+    /// group, e.g. through `render_with_bind_groups`, and the renderer's own
+    /// camera, clip and grid uniforms are unused. Returns the validation error
+    /// when the layout does not match the shader.
+    pub fn new_with_layout(
+        device: &Device,
+        texture_format: &TextureFormat,
+        pipeline_layout: PipelineLayout
+    ) -> Result<Self, LyonError> {
+        Self::builder().build_with_layout(device, texture_format, pipeline_layout)
+    }
+
+    /// Create a renderer drawing with a custom WGSL shader whose render
+    /// pipeline entry points are `vertex_entry` and `fragment_entry`, e.g.
+    /// `vs_main` and `fs_main` like the crate's. See `LyonRendererBuilder::shader`.
+    /// Returns wgpu's validation error when the shader does not compile or
+    /// lacks either entry point.
+    pub fn new_with_shader(
+        device: &Device,
+        texture_format: &TextureFormat,
+        wgsl: impl Into<Cow<'static, str>>,
+        vertex_entry: &str,
+        fragment_entry: &str
+    ) -> Result<Self, LyonError> {
+        Self::builder().shader(wgsl).entry_points(vertex_entry, fragment_entry).try_build(device, texture_format)
+    }
+
+    /// The bindings of group 0 read by the shader: the camera with a dynamic
+    /// offset, the clip rect as min and max corners, the grid uniform, the two
+    /// stereo cameras, then the shape colors of `VertexLayout::Uniform`.
+    pub fn camera_layout_entries() -> [BindGroupLayoutEntry; 5] {
+        [BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: BufferSize::new(Self::CAMERA_SIZE),
+            },
+            count: None,
+        }, BindGroupLayoutEntry {
+            binding: 1,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }, BindGroupLayoutEntry {
+            binding: 2,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }, BindGroupLayoutEntry {
+            binding: 3,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(2 * Self::CAMERA_SIZE),
+            },
+            count: None,
+        }, BindGroupLayoutEntry {
+            binding: 4,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }]
+    }
+
+    /// Compile `wgsl`, or the crate's shader without it.
+    ///
+    /// Every call makes a new module: wgpu's GL backend caches programs by
+    /// module and entry point, ignoring override constants, so pipelines with
+    /// different constants need modules of their own.
+    fn create_shader(device: &Device, wgsl: Option<&Cow<'static, str>>) -> ShaderModule {
+        match wgsl {
+            Some(wgsl) => device.create_shader_module(ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(wgsl.clone()),
+            }),
+            None => device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"))
+        }
+    }
+
+    fn create_stereo_shader(device: &Device) -> ShaderModule {
+        device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("stereo.wgsl"),
+            source: ShaderSource::Wgsl(concat!(include_str!("shader.wgsl"), include_str!("stereo.wgsl")).into()),
+        })
+    }
+
+    fn from_builder(
+        builder: LyonRendererBuilder,
+        device: &Device,
+        texture_format: &TextureFormat,
+        pipeline_layout: Option<PipelineLayout>
+    ) -> Self {
+        let shader = Self::create_shader(device, builder.shader.as_ref());
+        let camera_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &Self::camera_layout_entries(),
+        });
+        let custom_layout = pipeline_layout.is_some();
+        let pipeline_layout = pipeline_layout.unwrap_or_else(|| device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        }));
+        let depth_stencil = builder.depth_stencil.map(|mut depth_stencil| {
+            if let Some(depth_compare) = builder.depth_compare {
+                depth_stencil.depth_compare = depth_compare;
+            } else if builder.reverse_z {
+                depth_stencil.depth_compare = match depth_stencil.depth_compare {
+                    CompareFunction::Less => CompareFunction::Greater,
+                    CompareFunction::LessEqual => CompareFunction::GreaterEqual,
+                    depth_compare => depth_compare
+                };
+            }
+            if let Some(depth_write_enabled) = builder.depth_write_enabled {
+                depth_stencil.depth_write_enabled = depth_write_enabled;
+            }
+            depth_stencil
+        });
+        let config = PipelineConfig{
+            texture_format: *texture_format,
+            multisample: builder.multisample,
+            depth_stencil,
+            lighting: builder.lighting,
+            color_interpolation: builder.color_interpolation,
+            shading: builder.shading,
+            aa_clip: builder.aa_clip,
+            clip_feather: builder.clip_feather,
+            color_writes: builder.color_writes,
+            tinted: false,
+            reverse_z: builder.reverse_z,
+            unclipped_depth: builder.unclipped_depth,
+            multiview: None,
+            vertex_layout: builder.vertex_layout,
+            uniform_normals: builder.lighting.is_some(),
+            material_ids: builder.material_ids,
+            entry_points: builder.entry_points.clone()
+        };
+        assert!(
+            !config.unclipped_depth || device.features().contains(Features::DEPTH_CLIP_CONTROL),
+            "Unclipped depth needs Features::DEPTH_CLIP_CONTROL"
+        );
+        let render_pipeline = config.create_render_pipeline(device, &shader, &pipeline_layout);
+        let picking_pipeline = builder.picking.then(|| config.create_picking_pipeline(device, &shader, &pipeline_layout));
+        let tinted = builder.tinting.then(|| {
+            //Its own module for the premultiplied variant, see `create_shader`
+            let shader = Self::create_shader(device, None);
+            let pipeline = config.create_tinted_pipeline(device, &shader, &pipeline_layout);
+            (shader, pipeline)
+        });
+        let transparent_pipeline = builder.sorted
+            .then(|| config.create_transparent_pipeline(device, &shader, &pipeline_layout));
+        let depth_only_pipeline = builder.depth_only.then(|| {
+            assert!(config.depth_stencil.is_some(), "Depth only rendering needs a depth stencil state");
+            config.create_depth_only_pipeline(device, &shader, &pipeline_layout)
+        });
+        let mask_pipelines = builder.clip_masks.then(|| {
+            let format = config.depth_stencil.as_ref().map(|depth_stencil| depth_stencil.format);
+            assert!(format.is_some_and(|format| format.has_stencil_aspect()), "Clip masks need a depth stencil format, got {:?}", format);
+            (
+                config.create_mask_pipeline(device, &shader, &pipeline_layout),
+                config.create_masked_pipeline(device, &shader, &pipeline_layout)
+            )
+        });
+        let debug_pipeline = builder.debug.then(|| config.create_debug_pipeline(device, &shader, &pipeline_layout));
+        let stereo = builder.stereo.then(|| {
+            assert!(device.features().contains(Features::MULTIVIEW), "Stereo rendering needs Features::MULTIVIEW");
+            let shader = Self::create_stereo_shader(device);
+            let pipeline = config.create_stereo_pipeline(device, &shader, &pipeline_layout);
+            (shader, pipeline)
+        });
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let camera_stride = Self::CAMERA_SIZE.div_ceil(alignment) * alignment;
+        let (camera_buffer, camera_buffer_size) = Self::create_oversized_buffer(
+            device, None, &Self::camera_bytes(&[IDENTITY], camera_stride), BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        );
+        let (clip_buffer, _) = Self::create_oversized_buffer(
+            device, None, bytemuck::cast_slice(&Self::NO_CLIP), BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        );
+        let (grid_buffer, _) = Self::create_oversized_buffer(
+            device, None, &[0u8; std::mem::size_of::<[f32; 12]>()], BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        );
+        let (stereo_buffer, _) = Self::create_oversized_buffer(
+            device, None, bytemuck::cast_slice(&[IDENTITY, IDENTITY]), BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        );
+        //Pipelines without the `vs_uniform` entry point accept a smaller buffer
+        let shape_color_size = match builder.vertex_layout {
+            VertexLayout::Uniform => Self::MAX_UNIFORM_SHAPES,
+            _ => 1
+        } * std::mem::size_of::<[f32; 4]>();
+        let (shape_color_buffer, _) = Self::create_oversized_buffer(
+            device, None, &vec![0u8; shape_color_size], BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        );
+        let camera_bind_group = Self::create_camera_bind_group(
+            device, &camera_layout, &camera_buffer, [&clip_buffer, &grid_buffer, &stereo_buffer, &shape_color_buffer]
+        );
+
+        let vertex_buffer_size = Self::next_copy_buffer_size(4096);
+        let vertex_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: vertex_buffer_size,
+            usage: Self::vertex_buffer_usage(builder.combined_buffer),
+            mapped_at_creation: false,
+        });
+
+        //Never bound when the indices follow the vertices
+        let index_buffer_size = if builder.combined_buffer {0} else {Self::next_copy_buffer_size(4096)};
+        let index_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: index_buffer_size,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        LyonRenderer{
+            shader,
+            shader_source: builder.shader,
+            pipeline_layout,
+            custom_layout,
+            config,
+            render_pipeline,
+            camera_layout,
+            camera_stride,
+            camera_buffer_size,
+            camera_buffer,
+            camera_bind_group,
+            transform_buffer: None,
+            clip_buffer,
+            clip_scissor: None,
+            grid_buffer,
+            grid_pipeline: None,
+            outline: None,
+            outline_buffers: None,
+            picking_pipeline,
+            tinted,
+            transparent_pipeline,
+            depth_only_pipeline,
+            mask_pipelines,
+            debug_pipeline,
+            stereo_buffer,
+            stereo,
+            shape_color_buffer,
+            user_bind_groups: Vec::new(),
+            empty_bind_groups: Vec::new(),
+            vertex_buffer_size,
+            vertex_buffer,
+            packed_vertices: Vec::new(),
+            index_buffer_size,
+            index_buffer,
+            combined_buffer: builder.combined_buffer,
+            index_offset: 0,
+            frame: TessellatedFrame::default(),
+            keep_cpu_geometry: builder.keep_cpu_geometry,
+            geometry_freed: false,
+            clips: Vec::new(),
+            reallocated: false,
+            uploaded_bytes: 0,
+            target_size: None,
+            bind_when_empty: builder.bind_when_empty,
+            max_buffer_bytes: builder.max_buffer_bytes,
+            max_shapes: builder.max_shapes,
+            truncated: 0,
+            sanitize: builder.sanitize,
+            coordinate_transform: builder.coordinate_transform,
+            position_quantization: builder.position_quantization,
+            tessellation_threads: builder.tessellation_threads,
+            vertex_layout: builder.vertex_layout,
+            fill_options: builder.fill_options,
+            auto_retessellate: builder.auto_retessellate,
+            camera_scale: 1.0,
+            camera: IDENTITY,
+            retained: None
+        }
+    }
+
+    /// Rebuild the pipeline for a new target format, keeping buffers and geometry intact.
+    ///
+    /// Returns `UnsupportedSampleCount` unless the sample count is one the
+    /// format is guaranteed to support, like `supported_sample_counts`. Counts
+    /// the adapter only offers through `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`
+    /// are rejected, as the device cannot report them.
+    pub fn set_texture_format(
+        &mut self,
+        device: &Device,
+        texture_format: &TextureFormat
+    ) -> Result<(), LyonError> {
+        let count = self.config.multisample.count;
+        let features = texture_format.guaranteed_format_features(device.features());
+        if !features.flags.sample_count_supported(count) {
+            return Err(LyonError::UnsupportedSampleCount{format: *texture_format, count});
+        }
+
+        self.config.texture_format = *texture_format;
+        self.rebuild_pipeline(device);
+        Ok(())
+    }
+
+    pub fn texture_format(&self) -> TextureFormat {self.config.texture_format}
+
+    /// The multisample counts `format` is guaranteed to support on `device`,
+    /// always including 1. Check a depth format the same way when using one.
+    pub fn supported_sample_counts(device: &Device, format: TextureFormat) -> Vec<u32> {
+        format.guaranteed_format_features(device.features()).flags.supported_sample_counts()
+    }
+
+    /// Set the size in pixels of the render target. Once set, `render` resets
+    /// the scissor to the whole target when it is done.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.target_size = Some((width, height));
+    }
+
+    /// Compile `wgsl` and rebuild the pipelines with it, keeping the buffers,
+    /// geometry and uniforms, e.g. to hot reload a shader during development.
+    /// It replaces the shader like the builder's `shader`, with the same
+    /// entry points. If it fails to compile or build a pipeline, the previous
+    /// shader stays and wgpu's error is returned. On the web errors are not
+    /// caught.
+    pub fn reload_shader(&mut self, device: &Device, wgsl: &str) -> Result<(), LyonError> {
+        let previous = self.shader_source.replace(wgsl.to_string().into());
+        let result = validated(device, || self.rebuild_pipeline(device));
+        if let Err(message) = result {
+            //Some pipelines may have been built from the failed module
+            self.shader_source = previous;
+            self.rebuild_pipeline(device);
+            return Err(LyonError::Shader{message});
+        }
+        Ok(())
+    }
+
+    /// Enable or disable directional lighting. Normals are only computed by
+    /// `prepare` while lighting is enabled. A `VertexLayout::Uniform` renderer
+    /// built without lighting uploads no normals, so its shapes are lit as if
+    /// facing the viewer.
+    pub fn set_lighting(&mut self, device: &Device, lighting: Option<Lighting>) {
+        self.config.lighting = lighting;
+        self.rebuild_pipeline(device);
+    }
+
+    /// Add the caller's bind group layouts to the pipeline layout at the given
+    /// indices, for use by a custom shader. Unused indices in between are filled
+    /// with empty groups. Removes any previously set user bind groups, and
+    /// replaces a layout passed to `new_with_layout`.
+    pub fn set_user_bind_group_layouts(
+        &mut self,
+        device: &Device,
+        layouts: &[(u32, &BindGroupLayout)]
+    ) -> Result<(), LyonError> {
+        if let Some((index, _)) = layouts.iter().find(|(i, _)| Self::RESERVED_BIND_GROUPS.contains(i)) {
+            return Err(LyonError::ReservedBindGroup{index: *index});
+        }
+
+        let empty_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[],
+        });
+        let count = layouts.iter().map(|(i, _)| *i + 1).max().unwrap_or(1);
+        let mut bind_group_layouts = vec![&self.camera_layout];
+        self.empty_bind_groups.clear();
+        for index in 1..count {
+            match layouts.iter().find(|(i, _)| *i == index) {
+                Some((_, layout)) => bind_group_layouts.push(layout),
+                None => {
+                    bind_group_layouts.push(&empty_layout);
+                    self.empty_bind_groups.push((index, device.create_bind_group(&BindGroupDescriptor {
+                        label: None,
+                        layout: &empty_layout,
+                        entries: &[],
+                    })));
+                }
+            }
+        }
+
+        self.pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        self.user_bind_groups.clear();
+        self.custom_layout = false;
+        self.rebuild_pipeline(device);
+        Ok(())
+    }
+
+    /// Bind a group at `index` before drawing; its layout must have been
+    /// passed to `set_user_bind_group_layouts`.
+    pub fn set_user_bind_group(&mut self, index: u32, bind_group: Arc<BindGroup>) -> Result<(), LyonError> {
+        if Self::RESERVED_BIND_GROUPS.contains(&index) {
+            return Err(LyonError::ReservedBindGroup{index});
+        }
+        self.user_bind_groups.retain(|(i, _)| *i != index);
+        self.user_bind_groups.push((index, bind_group));
+        Ok(())
+    }
+
+    /// Upload the column major camera matrices used by `render_with_camera`;
+    /// `render` uses the first. Defaults to a single identity camera.
+    ///
+    /// The cameras are written through the queue, so every pass in a submission
+    /// sees the values from the last call made before that submission.
+    ///
+    /// With the builder's `auto_retessellate_on_scale` the shapes of the last
+    /// `prepare` are tessellated and uploaded again when the scale of the first
+    /// camera, from its 2D part, has moved past `RETESSELLATE_RATIO`. They keep
+    /// the clips of that `prepare`, but vertex hooks and `set_shape_bound` are
+    /// not applied again. If tessellating fails the previous geometry stays.
+    pub fn set_cameras(&mut self, device: &Device, queue: &Queue, cameras: &[[[f32; 4]; 4]]) {
+        let cameras = if cameras.is_empty() {&[IDENTITY]} else {cameras};
+        self.camera = cameras[0];
+        if self.auto_retessellate {
+            let [x, y, ..] = cameras[0];
+            let scale = (x[0] * y[1] - y[0] * x[1]).abs().sqrt();
+            if scale.is_normal() {
+                self.camera_scale = scale;
+                self.retessellate_for_scale(device, queue);
+            }
+        }
+        let contents = Self::camera_bytes(cameras, self.camera_stride);
+        if self.camera_buffer_size >= contents.len() as u64 {
+            Self::write_buffer(queue, &self.camera_buffer, &contents);
+        } else {
+            let (camera_buffer, camera_buffer_size) = Self::create_oversized_buffer(
+                device, None, &contents, BufferUsages::UNIFORM | BufferUsages::COPY_DST
+            );
+            self.camera_bind_group = Self::create_camera_bind_group(
+                device, &self.camera_layout, &camera_buffer, self.uniform_buffers()
+            );
+            self.camera_buffer = camera_buffer;
+            self.camera_buffer_size = camera_buffer_size;
+        }
+    }
+
+    fn retessellate_for_scale(&mut self, device: &Device, queue: &Queue) {
+        let Some(retained) = &self.retained else {return;};
+        let ratio = self.camera_scale / retained.scale;
+        if ratio < Self::RETESSELLATE_RATIO && ratio > 1.0 / Self::RETESSELLATE_RATIO {return;}
+
+        let retained = self.retained.take().unwrap();
+        let fill_options = Self::scaled_fill_options(&retained.fill_options, self.camera_scale);
+        let settings = TessellationSettings{clips: &retained.clips, ..self.settings(&fill_options)};
+        let mut frame = TessellatedFrame::default();
+        let result = frame.tessellate_threaded(
+            &mut FillTessellator::new(), &retained.shapes, settings, self.tessellation_threads
+        );
+        //Recorded as tessellated either way, so a failing scale is not retried every frame
+        let retained = RetainedShapes{scale: self.camera_scale, ..retained};
+        if result.is_err() || self.upload(device, queue, frame).is_ok() {
+            self.retained = Some(retained);
+        }
+    }
+
+    /// The tolerance shrinks as the camera zooms in, so it stays the same on screen.
+    fn scaled_fill_options(fill_options: &FillOptions, scale: f32) -> FillOptions {
+        fill_options.with_tolerance(fill_options.tolerance / scale)
+    }
+
+    /// Set the RGB color and z of each shape for a renderer built with
+    /// `VertexLayout::Uniform`, by shape index. Every render path draws a shape
+    /// at its z, and all but `render_picking`, `render_debug` and
+    /// `render_depth_only`, which only take the z, also color it.
+    pub fn set_shape_colors(&self, queue: &Queue, colors: &[([f32; 3], f32)]) {
+        assert!(self.config.uniform_colors(), "Shape colors need a renderer built with VertexLayout::Uniform");
+        assert!(colors.len() <= Self::MAX_UNIFORM_SHAPES, "At most {} shape colors fit", Self::MAX_UNIFORM_SHAPES);
+        let colors: Vec<[f32; 4]> = colors.iter().map(|([r, g, b], z)| [*r, *g, *b, *z]).collect();
+        Self::write_buffer(queue, &self.shape_color_buffer, bytemuck::cast_slice(&colors));
+    }
+
+    /// Upload the column major left and right eye cameras used by `render_stereo`.
+    pub fn set_stereo_cameras(&mut self, queue: &Queue, left: [[f32; 4]; 4], right: [[f32; 4]; 4]) {
+        Self::write_buffer(queue, &self.stereo_buffer, bytemuck::cast_slice(&[left, right]));
+    }
+
+    /// Clip every shape of the following `prepare` calls to `bound`, on top of
+    /// any clips already pushed. Shapes outside the intersection are not drawn.
+    pub fn push_clip(&mut self, bound: Bound) {
+        self.clips.push(bound);
+    }
+
+    pub fn pop_clip(&mut self) -> Option<Bound> {
+        self.clips.pop()
+    }
+
+    /// Move the scissor of a prepared shape, e.g. to animate a sliding panel,
+    /// without tessellating or uploading again. The bound is clipped by the
+    /// clips pushed now and replaces any normalized or union bounds. Shapes
+    /// clipped away entirely by `prepare` have no geometry to show. Returns
+    /// false, changing nothing, if the last `prepare` had no shape at
+    /// `shape_index`.
+    pub fn set_shape_bound(&mut self, shape_index: usize, bound: Bound) -> bool {
+        let Some(draw) = self.frame.draws.get_mut(shape_index) else {return false;};
+        draw.bound = self.clips.iter().try_fold(bound, |bound, clip| intersect_bounds(bound, *clip))
+            .unwrap_or((0, 0, 0, 0));
+        draw.normalized = None;
+        draw.union.1 = draw.union.0;
+        true
+    }
+
+    /// Clip everything drawn by `render` to a fractional `[x, y, width, height]`
+    /// rect in target pixels, or stop clipping with `None`.
+    ///
+    /// With the builder's `aa_clip` the scissor is rounded out to every pixel the
+    /// shader's fade reaches, otherwise it is rounded to the nearest pixel.
+    pub fn set_aa_clip(&mut self, queue: &Queue, clip: Option<[f32; 4]>) {
+        let Some([x, y, width, height]) = clip else {
+            self.clip_scissor = None;
+            Self::write_buffer(queue, &self.clip_buffer, bytemuck::cast_slice(&Self::NO_CLIP));
+            return;
+        };
+        let rect = [x, y, x + width, y + height];
+        let [left, top, right, bottom] = if self.config.aa_clip {
+            //Pixels whose centers are within half the feather outside the rect
+            let reach = self.config.clip_feather / 2.0 - 0.5;
+            [(rect[0] - reach).floor(), (rect[1] - reach).floor(), (rect[2] + reach).ceil(), (rect[3] + reach).ceil()]
+        } else {
+            rect.map(f32::round)
+        };
+        let [left, top, right, bottom] = [left, top, right, bottom].map(|edge| edge.max(0.0) as u32);
+        self.clip_scissor = Some((left, top, right.saturating_sub(left), bottom.saturating_sub(top)));
+        Self::write_buffer(queue, &self.clip_buffer, bytemuck::cast_slice(&rect));
+    }
+
+    /// Set the grid drawn by `render_grid`, or remove it with `None`. The grid
+    /// pipeline is only built while a grid is set.
+    pub fn set_grid(&mut self, device: &Device, queue: &Queue, grid: Option<Grid>) {
+        let Some(grid) = grid else {
+            self.grid_pipeline = None;
+            return;
+        };
+        Self::write_buffer(queue, &self.grid_buffer, bytemuck::cast_slice(&grid.uniform()));
+        if self.grid_pipeline.is_none() {
+            self.grid_pipeline = Some(self.config.create_grid_pipeline(device, &self.shader, &self.pipeline_layout));
+        }
+    }
+
+    /// Set the outline drawn by `render_outline`, or remove it with `None`. It
+    /// is built from the shape's fill, leaving out its shadow, and rebuilt by
+    /// every `prepare` so it follows the shape.
+    pub fn set_outline(&mut self, device: &Device, outline: Option<Outline>) {
+        self.outline = outline;
+        self.update_outline(device);
+    }
+
+    fn update_outline(&mut self, device: &Device) {
+        self.outline_buffers = self.outline.filter(|_| !self.geometry_freed).and_then(|outline| {
+            let draw = self.frame.draws.get(outline.shape)?;
+            let base_vertex = draw.base_vertex as usize;
+            let vertex_end = self.frame.draws.get(outline.shape + 1)
+                .map_or(self.frame.buffers.vertices.len(), |next| next.base_vertex as usize);
+            let (vertices, indices) = outline_ring(
+                &self.frame.buffers.vertices[base_vertex..vertex_end],
+                &self.frame.buffers.indices[(draw.start + draw.shadow_indices) as usize..draw.end as usize],
+                outline.color,
+                outline.width
+            );
+            if indices.is_empty() {return None;}
+            let mut packed = Vec::new();
+            let vertices = Vertex::gpu_bytes(self.config.gpu_vertex(), &vertices, &mut packed);
+            self.uploaded_bytes += (vertices.len() + std::mem::size_of_val(&indices[..])) as u64;
+            let (vertex_buffer, _) = Self::create_oversized_buffer(device, None, vertices, BufferUsages::VERTEX);
+            let (index_buffer, _) = Self::create_oversized_buffer(
+                device, None, bytemuck::cast_slice(&indices), BufferUsages::INDEX
+            );
+            Some((vertex_buffer, index_buffer, indices.len() as u32))
+        });
+    }
+
+    /// Prepare for rendering this frame; create all resources that will be
+    /// used during the next render that do not already exist.
+    ///
+    /// Each shape's indices are stored relative to its first vertex, which is
+    /// passed as the `base_vertex` of its draw. A frame can hold any number of
+    /// vertices with `u16` indices, but a single shape over 65536 vertices
+    /// fails with `TooManyVertices`.
+    ///
+    /// On error nothing is drawn until the next successful `prepare`.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        fill_options: &FillOptions,
+        shapes: Vec<Shape>
+    ) -> Result<(), LyonError> {
+        self.prepare_shapes(device, queue, &mut FillTessellator::new(), fill_options, shapes, None)
+    }
+
+    /// Like `prepare`, running `hook` over every vertex between tessellating
+    /// and uploading, see `TessellatedFrame::map_vertices`.
+    pub fn prepare_with_hook(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        fill_options: &FillOptions,
+        shapes: Vec<Shape>,
+        mut hook: impl FnMut(&mut Vertex)
+    ) -> Result<(), LyonError> {
+        self.prepare_shapes(device, queue, &mut FillTessellator::new(), fill_options, shapes, Some(&mut hook))
+    }
+
+    /// Like `prepare`, with the fill options from the builder or `set_fill_options`.
+    pub fn prepare_default(&mut self, device: &Device, queue: &Queue, shapes: Vec<Shape>) -> Result<(), LyonError> {
+        let fill_options = self.fill_options;
+        self.prepare(device, queue, &fill_options, shapes)
+    }
+
+    /// Set the fill options used by `prepare_default` from the next call on.
+    pub fn set_fill_options(&mut self, fill_options: FillOptions) {
+        self.fill_options = fill_options;
+    }
+
+    pub fn fill_options(&self) -> FillOptions {self.fill_options}
+
+    /// Like `prepare`, but tessellates with the caller's scratch state and drains
+    /// `shapes` instead of consuming it, so both keep their allocations for the
+    /// next frame.
+    ///
+    /// Scratch space that is too small for a frame grows and stays grown, so it
+    /// stops allocating once the largest frame has been prepared. lyon's path
+    /// builder still allocates an attribute buffer per shape, as do lighting
+    /// normals and shadows for the shapes that use them.
+    pub fn prepare_with_scratch(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        fill_options: &FillOptions,
+        shapes: &mut Vec<Shape>,
+        scratch: &mut PrepareScratch
+    ) -> Result<(), LyonError> {
+        self.prepare_shapes(device, queue, &mut scratch.tessellator, fill_options, shapes.drain(..), None)
+    }
+
+    fn prepare_shapes(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        tessellator: &mut FillTessellator,
+        fill_options: &FillOptions,
+        shapes: impl IntoIterator<Item = Shape>,
+        hook: Option<&mut dyn FnMut(&mut Vertex)>
+    ) -> Result<(), LyonError> {
+        //Reuse the previous frame's allocations
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.clear();
+        let mut all_shapes = shapes.into_iter();
+        let shapes = all_shapes.by_ref().take(self.max_shapes);
+        let (result, retained) = if self.auto_retessellate {
+            let shapes: Vec<Shape> = shapes.collect();
+            let fill_options = Self::scaled_fill_options(fill_options, self.camera_scale);
+            let result = frame.tessellate_threaded(
+                tessellator, &shapes, self.settings(&fill_options), self.tessellation_threads
+            );
+            (result, Some(shapes))
+        } else {
+            (frame.tessellate_threaded(tessellator, shapes, self.settings(fill_options), self.tessellation_threads), None)
+        };
+        self.truncated = all_shapes.count();
+        if let Err(error) = result {
+            frame.clear();
+            self.frame = frame;
+            self.reallocated = false;
+            self.retained = None;
+            return Err(error);
+        }
+        if let Some(hook) = hook {
+            frame.map_vertices(hook);
+        }
+        self.upload(device, queue, frame)?;
+        self.retained = retained.map(|shapes| RetainedShapes{
+            shapes, fill_options: *fill_options, clips: self.clips.clone(), scale: self.camera_scale
+        });
+        Ok(())
+    }
+
+    /// Tessellate shapes on the CPU without touching any GPU resources, using
+    /// the current clip stack and lighting.
+    pub fn tessellate(
+        &self,
+        fill_options: &FillOptions,
+        shapes: Vec<Shape>
+    ) -> Result<TessellatedFrame, LyonError> {
+        let mut frame = TessellatedFrame::default();
+        frame.tessellate_threaded(
+            &mut FillTessellator::new(), shapes, self.settings(fill_options), self.tessellation_threads
+        )?;
+        Ok(frame)
+    }
+
+    fn settings<'a>(&'a self, fill_options: &'a FillOptions) -> TessellationSettings<'a> {
+        TessellationSettings{
+            fill_options,
+            clips: &self.clips,
+            normals: self.config.lighting.is_some(),
+            sanitize: self.sanitize,
+            layout: self.vertex_layout,
+            transform: self.coordinate_transform,
+            quantization: self.position_quantization,
+            pixel_grid: self.pixel_grid()
+        }
+    }
+
+    /// From positions to target pixels through the affine part of the first
+    /// camera, or `None` before the target size is known.
+    fn pixel_grid(&self) -> Option<Transform> {
+        let (width, height) = self.target_size?;
+        let [x, y, _, translation] = self.camera;
+        let camera = Transform::new(x[0], x[1], y[0], y[1], translation[0], translation[1]);
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        Some(camera.then(&Transform::new(half_width, 0.0, 0.0, -half_height, half_width, half_height)))
+    }
+
+    /// Upload a tessellated frame to be drawn by the next render.
+    ///
+    /// On error nothing is drawn until the next successful upload.
+    pub fn upload(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frame: TessellatedFrame
+    ) -> Result<(), LyonError> {
+        let result = self.upload_frame(device, queue, frame);
+        self.update_outline(device);
+        if !self.keep_cpu_geometry && !self.frame.is_empty() {
+            self.frame.free_geometry();
+            self.packed_vertices = Vec::new();
+            self.geometry_freed = true;
+        }
+        result
+    }
+
+    fn upload_frame(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frame: TessellatedFrame
+    ) -> Result<(), LyonError> {
+        self.reallocated = false;
+        self.uploaded_bytes = 0;
+        self.frame = frame;
+        self.geometry_freed = false;
+        //A frame from elsewhere no longer matches the retained shapes
+        self.retained = None;
+        if self.config.uniform_colors() && self.frame.draws.len() > Self::MAX_UNIFORM_SHAPES {
+            let count = self.frame.draws.len();
+            self.frame.clear();
+            return Err(LyonError::TooManyShapes{count, max: Self::MAX_UNIFORM_SHAPES});
+        }
+        if self.frame.is_empty() {return Ok(());}
+        self.upload_transforms(device, queue);
+        if self.combined_buffer {return self.upload_combined(device, queue);}
+
+        let vertex_size = (self.frame.buffers.vertices.len() * Vertex::gpu_size(self.config.gpu_vertex())) as u64;
+        let vertex_bytes = (vertex_size > self.vertex_buffer_size).then(|| Self::next_copy_buffer_size(vertex_size));
+        let index_bytes = Self::allocation_size(self.index_buffer_size, &self.frame.buffers.indices);
+        if let Some(size) = vertex_bytes.max(index_bytes).filter(|size| *size > self.max_buffer_bytes) {
+            self.frame.clear();
+            return Err(LyonError::BufferTooLarge{size, max: self.max_buffer_bytes});
+        }
+
+        let vertices_raw = Vertex::gpu_bytes(self.config.gpu_vertex(), &self.frame.buffers.vertices, &mut self.packed_vertices);
+        self.uploaded_bytes += vertices_raw.len() as u64;
+        if self.vertex_buffer_size >= vertices_raw.len() as u64 {
+            Self::write_buffer(queue, &self.vertex_buffer, vertices_raw);
+        } else {
+            let (vertex_buffer, vertex_buffer_size) = Self::create_oversized_buffer(
+                device, None, vertices_raw, BufferUsages::VERTEX | BufferUsages::COPY_DST
+            );
+            self.vertex_buffer = vertex_buffer;
+            self.vertex_buffer_size = vertex_buffer_size;
+            self.reallocated = true;
+        }
+
+        let indices_raw = bytemuck::cast_slice(&self.frame.buffers.indices);
+        self.uploaded_bytes += indices_raw.len() as u64;
+        if self.index_buffer_size >= indices_raw.len() as u64 {
+            Self::write_buffer(queue, &self.index_buffer, indices_raw);
+        } else {
+            let (index_buffer, index_buffer_size) = Self::create_oversized_buffer(
+                device, None, indices_raw, BufferUsages::INDEX | BufferUsages::COPY_DST
+            );
+            self.index_buffer = index_buffer;
+            self.index_buffer_size = index_buffer_size;
+            self.reallocated = true;
+        }
+        Ok(())
+    }
+
+    /// Pack the frame's shape transforms densely, one per dynamic offset, into
+    /// the transform buffer, growing it when they do not fit.
+    fn upload_transforms(&mut self, device: &Device, queue: &Queue) {
+        if self.frame.transforms.is_empty() || self.custom_layout {return;}
+        let contents = Self::camera_bytes(&self.frame.transforms, self.camera_stride);
+        match &self.transform_buffer {
+            Some((buffer, _)) if buffer.size() >= contents.len() as u64 => Self::write_buffer(queue, buffer, &contents),
+            _ => {
+                let (buffer, _) = Self::create_oversized_buffer(
+                    device, None, &contents, BufferUsages::UNIFORM | BufferUsages::COPY_DST
+                );
+                let bind_group = Self::create_camera_bind_group(device, &self.camera_layout, &buffer, self.uniform_buffers());
+                self.transform_buffer = Some((buffer, bind_group));
+                self.reallocated = true;
+            }
+        }
+    }
+
+    fn upload_combined(&mut self, device: &Device, queue: &Queue) -> Result<(), LyonError> {
+        let vertices_raw = Vertex::gpu_bytes(self.config.gpu_vertex(), &self.frame.buffers.vertices, &mut self.packed_vertices);
+        let indices_raw: &[u8] = bytemuck::cast_slice(&self.frame.buffers.indices);
+        //Both the copy and the index buffer offset need the alignment
+        let index_offset = (vertices_raw.len() as u64).next_multiple_of(COPY_BUFFER_ALIGNMENT);
+        let size = (index_offset + indices_raw.len() as u64).next_multiple_of(COPY_BUFFER_ALIGNMENT);
+        let write = |contents: &mut [u8]| {
+            contents[..vertices_raw.len()].copy_from_slice(vertices_raw);
+            contents[index_offset as usize..][..indices_raw.len()].copy_from_slice(indices_raw);
+        };
+
+        if self.vertex_buffer_size >= size {
+            if let Some(mut contents) = queue.write_buffer_with(&self.vertex_buffer, 0, BufferSize::new(size).unwrap()) {
+                write(&mut contents);
+            }
+        } else {
+            let allocation = Self::next_copy_buffer_size(size);
+            if allocation > self.max_buffer_bytes {
+                self.frame.clear();
+                return Err(LyonError::BufferTooLarge{size: allocation, max: self.max_buffer_bytes});
+            }
+            let buffer = device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: allocation,
+                usage: Self::vertex_buffer_usage(true),
+                mapped_at_creation: true,
+            });
+            write(&mut buffer.slice(..size).get_mapped_range_mut());
+            buffer.unmap();
+            self.vertex_buffer = buffer;
+            self.vertex_buffer_size = allocation;
+            self.reallocated = true;
+        }
+        self.index_offset = index_offset;
+        self.uploaded_bytes = (vertices_raw.len() + indices_raw.len()) as u64;
+        Ok(())
+    }
+
+    fn vertex_buffer_usage(combined_buffer: bool) -> BufferUsages {
+        let usage = BufferUsages::VERTEX | BufferUsages::COPY_DST;
+        if combined_buffer {usage | BufferUsages::INDEX} else {usage}
+    }
+
+    /// The allocated (not used) sizes in bytes of the vertex and index buffers.
+    pub fn buffer_sizes(&self) -> (u64, u64) {(self.vertex_buffer_size, self.index_buffer_size)}
+
+    /// The attachments a pass needs for `render`, from the texture format and
+    /// the builder's multisample and depth stencil state.
+    pub fn pass_requirements(&self) -> PassRequirements {
+        let depth_stencil = self.config.depth_stencil.as_ref();
+        PassRequirements{
+            color_format: self.config.texture_format,
+            sample_count: self.config.multisample.count,
+            depth_format: depth_stencil.map(|depth_stencil| depth_stencil.format),
+            stencil: depth_stencil.is_some_and(|depth_stencil| depth_stencil.stencil.is_enabled())
+        }
+    }
+
+    /// The depth nothing has been drawn at, to clear depth attachments to:
+    /// `0.0` with the builder's `reverse_z` and `1.0` otherwise.
+    pub fn clear_depth(&self) -> f32 {
+        if self.config.reverse_z {0.0} else {1.0}
+    }
+
+    /// The allocated bytes of every buffer the renderer owns: vertices, indices,
+    /// cameras, shape transforms and the clip, grid, stereo and shape color
+    /// uniforms. Staging memory wgpu uses for queue writes, pipelines and
+    /// textures made by `warmup` are not included.
+    pub fn memory_usage(&self) -> usize {
+        let uniforms = self.camera_buffer_size + self.uniform_buffers().iter().map(|buffer| buffer.size()).sum::<u64>()
+            + self.transform_buffer.as_ref().map_or(0, |(buffer, _)| buffer.size());
+        (self.vertex_buffer_size + self.index_buffer_size + uniforms) as usize
+    }
+
+    /// How many shapes the last `prepare` dropped past the builder's `max_shapes`.
+    pub fn truncated_shapes(&self) -> usize {self.truncated}
+
+    /// The bytes of vertices and indices copied to the GPU since the start of
+    /// the last `prepare` or `upload`, whether written into the existing
+    /// buffers or into new ones. Outline geometry counts, uniforms do not.
+    pub fn bytes_uploaded_last_frame(&self) -> u64 {self.uploaded_bytes}
+
+    /// Whether the last `prepare` had to grow the vertex, index or shape
+    /// transform buffer.
+    pub fn last_prepare_reallocated(&self) -> bool {self.reallocated}
+
+    /// The triangles uploaded by the last `prepare`, e.g. for exporting to a mesh
+    /// format. Empty without the builder's `keep_cpu_geometry`.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        (!self.geometry_freed).then_some(&self.frame).into_iter().flat_map(TessellatedFrame::triangles)
+    }
+
+    /// Write the triangles uploaded by the last `prepare` to an SVG file, e.g. to
+    /// inspect the mesh in a browser. Writes no triangles without the builder's
+    /// `keep_cpu_geometry`. See `TessellatedFrame::export_svg`.
+    #[cfg(feature = "debug-export")]
+    pub fn export_svg(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        crate::write_svg(self.triangles(), path.as_ref())
+    }
+
+    /// The indices of shapes skipped by the builder's `sanitize` in the last `prepare`.
+    pub fn skipped_shapes(&self) -> &[usize] {self.frame.skipped_shapes()}
+
+    /// The lyon sources of every vertex tessellated by the last `prepare`, indexed
+    /// like the vertex buffer. Vertices created on flattened curves or at
+    /// intersections have `VertexSource::Edge` sources rather than a single endpoint.
+    #[cfg(feature = "debug-attributes")]
+    pub fn vertex_sources(&self) -> &[Vec<VertexSource>] {&self.frame.sources}
+
+    /// Render using caller provided render pass.
+    ///
+    /// Shapes are drawn strictly in the order they were passed to `prepare`
+    /// (or merged into a `TessellatedFrame`), one draw each, so without a depth
+    /// test later shapes paint over earlier ones.
+    ///
+    /// Geometry is always drawn as triangle lists, so the pipelines set no
+    /// `strip_index_format` and no primitive restart index is ever written.
+    /// Disconnected strokes need no restart either: `Shape::polyline` strokes
+    /// them into triangles of their own shape.
+    ///
+    /// Every `render` method sets the pipeline, the renderer's bind groups, the
+    /// vertex and index buffers and a scissor before each draw, so it can share
+    /// a pass with other renderers regardless of what they left bound, and
+    /// leaves a full target scissor behind. The viewport and stencil reference
+    /// are the caller's; only `render_tinted` sets the blend constant.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
+        self.render_with_camera(render_pass, 0);
+    }
+
+    /// Render the prepared geometry with one of the cameras from `set_cameras`.
+    pub fn render_with_camera(&self, render_pass: &mut RenderPass<'_>, camera: usize) {
+        self.draw(render_pass, &self.render_pipeline, camera, None, None, false);
+    }
+
+    /// Set the caller's `(index, bind_group, dynamic_offsets)` groups and render,
+    /// for renderers made with `new_with_layout`. With the renderer's own
+    /// layout the crate's groups are set over them.
+    pub fn render_with_bind_groups(&self, render_pass: &mut RenderPass<'_>, bind_groups: &[(u32, &BindGroup, &[u32])]) {
+        for (index, bind_group, offsets) in bind_groups {
+            render_pass.set_bind_group(*index, bind_group, offsets);
+        }
+        self.render(render_pass);
+    }
+
+    /// Bind `buffer` in place of the renderer's cameras for `render_with_transforms`.
+    /// The clip from `set_aa_clip` is still read from the renderer.
+    pub fn create_transform_bind_group(&self, device: &Device, buffer: &Buffer) -> BindGroup {
+        Self::create_camera_bind_group(device, &self.camera_layout, buffer, self.uniform_buffers())
+    }
+
+    /// Render with a column major transform per shape read from a caller managed
+    /// uniform buffer, bound by `create_transform_bind_group`. Each offset must be
+    /// a multiple of the device's `min_uniform_buffer_offset_alignment`
+    /// (usually 256 bytes).
+    ///
+    /// Panics if `offsets` does not hold one offset per shape of the last `prepare`.
+    pub fn render_with_transforms(&self, render_pass: &mut RenderPass<'_>, transforms: &BindGroup, offsets: &[u32]) {
+        assert_eq!(offsets.len(), self.frame.draws.len(), "Expected one transform offset per shape");
+        self.draw(render_pass, &self.render_pipeline, 0, None, Some((transforms, offsets)), false);
+    }
+
+    /// Redraw only the shapes overlapping `dirty`, scissored to it, for passes
+    /// that load the previous frame. Blended shapes are drawn again over their
+    /// old pixels, so the dirty region should be cleared (e.g. by a background
+    /// shape) for them to come out the same.
+    pub fn render_dirty(&self, render_pass: &mut RenderPass<'_>, dirty: Bound) {
+        self.render_clipped(render_pass, Some(dirty));
+    }
+
+    /// Render with `base_clip` intersected with every shape's bound, so a whole
+    /// layer can be clipped without tessellating it again. Shapes outside it are
+    /// skipped.
+    pub fn render_clipped(&self, render_pass: &mut RenderPass<'_>, base_clip: Option<Bound>) {
+        self.draw(render_pass, &self.render_pipeline, 0, base_clip, None, false);
+    }
+
+    /// Render only the prepared shapes in `shapes`, e.g. the shapes of one of
+    /// several callbacks sharing the renderer's buffers. Indices past the
+    /// prepared shapes are ignored.
+    pub fn render_shapes(&self, render_pass: &mut RenderPass<'_>, shapes: Range<usize>) {
+        let count = self.frame.draws.len();
+        RenderGroup{renderer: self, shapes: shapes.start.min(count)..shapes.end.min(count)}.render(render_pass);
+    }
+
+    /// Render with the viewport set to `viewport`, so the whole NDC range is
+    /// remapped into that region of the target, e.g. to draw a thumbnail of the
+    /// scene. Shape bounds stay in target pixels and are intersected with
+    /// `viewport`. The viewport is reset to the whole target from `resize`
+    /// afterwards, so nothing is drawn before `resize` or for an empty
+    /// `viewport`.
+    pub fn render_in_viewport(&self, render_pass: &mut RenderPass<'_>, viewport: Bound) {
+        let Some((target_width, target_height)) = self.target_size else {return;};
+        if viewport.2 == 0 || viewport.3 == 0 {return;}
+        let (x, y, width, height, min_depth, max_depth) = viewport.as_viewport();
+        render_pass.set_viewport(x, y, width, height, min_depth, max_depth);
+        self.draw(render_pass, &self.render_pipeline, 0, Some(viewport), None, false);
+        render_pass.set_viewport(0.0, 0.0, target_width as f32, target_height as f32, 0.0, 1.0);
+    }
+
+    /// Render each shape's index plus one into an `R32Uint` target, leaving zero
+    /// for the background, so reading back a pixel tells which shape is there.
+    /// Requires the builder's `picking`.
+    ///
+    /// Edges are not anti-aliased. With a depth test the pass needs its own
+    /// single sampled depth attachment.
+    pub fn render_picking(&self, render_pass: &mut RenderPass<'_>) {
+        let pipeline = self.picking_pipeline.as_ref().expect("Picking was not enabled on the builder");
+        self.draw(render_pass, pipeline, 0, None, None, true);
+    }
+
+    /// Render like `render` with the final fragment color, after gradients,
+    /// lighting and sRGB encoding, multiplied by the RGBA `tint`, e.g. to grey
+    /// out disabled widgets without tessellating again. Requires the builder's
+    /// `tinting`.
+    ///
+    /// The tint is premultiplied into the pass's blend constant, so each call
+    /// can use its own without uploading anything. Its alpha scales the color
+    /// and the coverage written to the target's alpha, while the target is
+    /// still kept by one minus the shape's own coverage, so fading a shape over
+    /// what is already drawn needs a cleared layer composited afterwards.
+    /// Targets that cannot blend are not tinted.
+    pub fn render_tinted(&self, render_pass: &mut RenderPass<'_>, tint: [f32; 4]) {
+        let (_, pipeline) = self.tinted.as_ref().expect("Tinting was not enabled on the builder");
+        let [r, g, b, a] = tint.map(f64::from);
+        render_pass.set_blend_constant(Color{r: r * a, g: g * a, b: b * a, a});
+        self.draw(render_pass, pipeline, 0, None, None, false);
+    }
+
+    /// Render only the depth of the prepared shapes, e.g. into a shadow map,
+    /// for a pass with a single sampled depth attachment and no color
+    /// attachments. Requires the builder's `depth_only`.
+    ///
+    /// Depth is tested and written like `render` does, from each vertex's z
+    /// through the camera, so translucent shapes and shadows write depth too.
+    pub fn render_depth_only(&self, render_pass: &mut RenderPass<'_>) {
+        let pipeline = self.depth_only_pipeline.as_ref().expect("Depth only rendering was not enabled on the builder");
+        self.draw(render_pass, pipeline, 0, None, None, false);
+    }
+
+    /// Render opaque shapes front to back with the render pipeline, then
+    /// transparent shapes back to front without writing depth, so they blend
+    /// over everything behind them. Requires the builder's `sorted` and a depth
+    /// stencil state that writes depth.
+    ///
+    /// A shape is transparent when any of its vertices, shadows included, has
+    /// an alpha below one. Shapes are sorted by the middle of their z range,
+    /// nearest first for the depth compare, keeping submission order at equal z.
+    /// `VertexLayout::Uniform` shapes all sort at z 0.
+    pub fn render_sorted(&self, render_pass: &mut RenderPass<'_>) {
+        let transparent_pipeline = self.transparent_pipeline.as_ref()
+            .expect("Sorted rendering was not enabled on the builder");
+        if !self.bind_frame(render_pass, &self.render_pipeline, 0) {return;}
+
+        let draws = &self.frame.draws;
+        let reverse_z = self.config.depth_stencil.as_ref().is_some_and(|depth_stencil| {
+            matches!(depth_stencil.depth_compare, CompareFunction::Greater | CompareFunction::GreaterEqual)
+        });
+        let nearest_first = |a: &usize, b: &usize| {
+            let order = draws[*a].z.total_cmp(&draws[*b].z);
+            if reverse_z {order.reverse()} else {order}
+        };
+        let (mut opaque, mut transparent): (Vec<usize>, Vec<usize>) = (0..draws.len()).partition(|i| draws[*i].opaque);
+        opaque.sort_by(nearest_first);
+        transparent.sort_by(|a, b| nearest_first(b, a));
+
+        for i in opaque {
+            self.draw_shape(render_pass, i, 0, None, None, false, false);
+        }
+        render_pass.set_pipeline(transparent_pipeline);
+        for i in transparent {
+            self.draw_shape(render_pass, i, 0, None, None, false, false);
+        }
+        self.reset_scissor(render_pass);
+    }
+
+    /// Render the prepared shapes like `render`, but colored by `mode` instead
+    /// of their vertex colors. Requires the builder's `debug`.
+    pub fn render_debug(&self, render_pass: &mut RenderPass<'_>, mode: DebugMode) {
+        let pipeline = self.debug_pipeline.as_ref().expect("Debug rendering was not enabled on the builder");
+        match mode {
+            DebugMode::ShapeIndex => self.draw(render_pass, pipeline, 0, None, None, true),
+        }
+    }
+
+    /// Render both eyes into a pass whose attachments are two layer array views,
+    /// using the cameras from `set_stereo_cameras`. Requires the builder's `stereo`.
+    ///
+    /// Scissors are shared by both views, so shape bounds and clips have to
+    /// cover a shape in either eye.
+    pub fn render_stereo(&self, render_pass: &mut RenderPass<'_>) {
+        let (_, pipeline) = self.stereo.as_ref().expect("Stereo was not enabled on the builder");
+        self.draw(render_pass, pipeline, 0, None, None, false);
+    }
+
+    /// Split the prepared shapes into at most `n` groups of consecutive shapes,
+    /// e.g. to record them into passes on several threads. Rendering every
+    /// group in order into the same target matches `render`. The groups borrow
+    /// the renderer, so they cannot outlive it or be kept across a `prepare`.
+    pub fn split_into_groups(&self, n: usize) -> Vec<RenderGroup<'_>> {
+        let shapes = self.frame.draws.len();
+        let group_size = shapes.div_ceil(n.max(1)).max(1);
+        (0..shapes).step_by(group_size).map(|start| RenderGroup{
+            renderer: self,
+            shapes: start..(start + group_size).min(shapes)
+        }).collect()
+    }
+
+    /// Record the prepared shapes into a render bundle for
+    /// `RenderPass::execute_bundles`, e.g. for a static scene. The bundle is
+    /// compatible with passes meeting `pass_requirements` and draws with the
+    /// first camera.
+    ///
+    /// Bundles cannot set scissors, so shape bounds and the clip scissor are
+    /// ignored; clip with the clip rect of `set_aa_clip` or a stencil instead.
+    /// The bundle keeps the buffers and bind groups it was recorded with, so
+    /// record it again after `prepare` reallocates or bind groups change.
+    pub fn record_bundle(&self, device: &Device) -> RenderBundle {
+        let depth_stencil = self.config.depth_stencil.as_ref().map(|depth_stencil| RenderBundleDepthStencil{
+            format: depth_stencil.format,
+            depth_read_only: depth_stencil.is_depth_read_only(),
+            stencil_read_only: depth_stencil.is_stencil_read_only(None),
+        });
+        let mut encoder = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor{
+            label: None,
+            color_formats: &[Some(self.config.texture_format)],
+            depth_stencil,
+            sample_count: self.config.multisample.count,
+            multiview: None,
+        });
+        encoder.set_pipeline(&self.render_pipeline);
+        if !self.custom_layout {
+            encoder.set_bind_group(0, &self.camera_bind_group, &[0]);
+            for (index, bind_group) in &self.empty_bind_groups {
+                encoder.set_bind_group(*index, bind_group, &[]);
+            }
+            for (index, bind_group) in &self.user_bind_groups {
+                encoder.set_bind_group(*index, bind_group, &[]);
+            }
+        }
+        encoder.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let indices = if self.combined_buffer {
+            self.vertex_buffer.slice(self.index_offset..)
+        } else {
+            self.index_buffer.slice(..)
+        };
+        encoder.set_index_buffer(indices, IndexFormat::Uint16);
+        for (i, draw) in self.frame.draws.iter().enumerate() {
+            if draw.start == draw.end {continue;}
+            let instances = if self.config.uniform_colors() {i as u32..i as u32 + 1} else {0..1};
+            let transform = self.transform_buffer.as_ref().zip(draw.transform);
+            if let Some(((_, bind_group), slot)) = transform {
+                encoder.set_bind_group(0, bind_group, &[(slot as u64 * self.camera_stride) as u32]);
+            }
+            encoder.draw_indexed(draw.start..draw.end, draw.base_vertex, instances);
+            if transform.is_some() {
+                encoder.set_bind_group(0, &self.camera_bind_group, &[0]);
+            }
+        }
+        encoder.finish(&RenderBundleDescriptor{label: None})
+    }
+
+    /// Draw the grid from `set_grid` as a single triangle covering the target,
+    /// seen through the first camera. Call it before `render` to keep it in
+    /// the background. Does nothing while no grid is set.
+    ///
+    /// The camera must not use perspective; the grid lies in the z = 0 plane.
+    pub fn render_grid(&self, render_pass: &mut RenderPass<'_>) {
+        let Some(grid_pipeline) = &self.grid_pipeline else {return;};
+        if !self.set_base_scissor(render_pass) {return;}
+        self.bind_pipeline(render_pass, grid_pipeline, 0);
+        render_pass.draw(0..3, 0..1);
+        self.reset_scissor(render_pass);
+    }
+
+    /// Draw the outline from `set_outline` over its shape with the first
+    /// camera. Call it after `render`. Does nothing while no outline is set or
+    /// its shape has no geometry.
+    ///
+    /// Only the clip scissor of `set_aa_clip` applies, not the shape's bound,
+    /// so the ring can reach past the shape. `VertexLayout::Uniform` shapes
+    /// keep their uniform color instead of the outline's.
+    pub fn render_outline(&self, render_pass: &mut RenderPass<'_>) {
+        let (Some(outline), Some((vertex_buffer, index_buffer, count))) = (&self.outline, &self.outline_buffers)
+            else {return;};
+        if !self.set_base_scissor(render_pass) {return;}
+        self.bind_pipeline(render_pass, &self.render_pipeline, 0);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+        let shape = outline.shape as u32;
+        let instances = if self.config.uniform_colors() {shape..shape + 1} else {0..1};
+        render_pass.draw_indexed(0..*count, 0, instances);
+        self.reset_scissor(render_pass);
+    }
+
+    fn draw(
+        &self,
+        render_pass: &mut RenderPass<'_>,
+        pipeline: &RenderPipeline,
+        camera: usize,
+        clip: Option<Bound>,
+        transforms: Option<(&BindGroup, &[u32])>,
+        shape_instances: bool
+    ) {
+        if !self.bind_frame(render_pass, pipeline, camera) {return;}
+        //The mask pipelines only stand in for the render pipeline
+        let masks = std::ptr::eq(pipeline, &self.render_pipeline);
+        for i in 0..self.frame.draws.len() {
+            self.draw_shape(render_pass, i, camera, clip, transforms, shape_instances, masks);
+        }
+        self.reset_scissor(render_pass);
+    }
+
+    /// Bind `pipeline` and the frame's buffers, or return false if there is
+    /// nothing to bind.
+    fn bind_frame(&self, render_pass: &mut RenderPass<'_>, pipeline: &RenderPipeline, camera: usize) -> bool {
+        if self.frame.is_empty() && !self.geometry_freed && !self.bind_when_empty {return false;}
+
+        let offset = camera as u64 * self.camera_stride;
+        assert!(offset + Self::CAMERA_SIZE <= self.camera_buffer_size, "Camera {} was never set", camera);
+
+        self.bind_pipeline(render_pass, pipeline, offset as u32);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let indices = if self.combined_buffer {
+            self.vertex_buffer.slice(self.index_offset..)
+        } else {
+            self.index_buffer.slice(..)
+        };
+        render_pass.set_index_buffer(indices, IndexFormat::Uint16);
+        true
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_shape(
+        &self,
+        render_pass: &mut RenderPass<'_>,
+        i: usize,
+        camera: usize,
+        clip: Option<Bound>,
+        transforms: Option<(&BindGroup, &[u32])>,
+        shape_instances: bool,
+        masks: bool
+    ) {
+        let DrawCall{start, end, base_vertex, bound, normalized, union, mask, transform, ..} = self.frame.draws[i];
+        if start == end {return;}
+        //Normalized bounds cannot be placed before the target size is known
+        let normalized = match normalized.map(|normalized| self.pixel_bound(normalized)) {
+            Some(None) => return,
+            normalized => normalized.flatten()
+        };
+        let union_bounds = &self.frame.union_bounds[union.0 as usize..union.1 as usize];
+        let mut bounds = std::iter::once(bound).chain(union_bounds.iter().copied()).filter_map(|bound| {
+            [normalized, clip, self.clip_scissor].into_iter().flatten().try_fold(bound, intersect_bounds)
+                .filter(|bound| bound.2 != 0 && bound.3 != 0)
+        }).peekable();
+        if bounds.peek().is_none() {return;}
+        if let Some((bind_group, offsets)) = transforms {
+            render_pass.set_bind_group(0, bind_group, &offsets[i..i+1]);
+        }
+        //A shape's own transform stands in for the camera until it is drawn
+        let transform = self.transform_buffer.as_ref().filter(|_| transforms.is_none()).zip(transform);
+        if let Some(((_, bind_group), slot)) = transform {
+            render_pass.set_bind_group(0, bind_group, &[(slot as u64 * self.camera_stride) as u32]);
+        }
+        //The picking, debug and uniform color shaders take the shape index from the instance index
+        let instances = if shape_instances || self.config.uniform_colors() {i as u32..i as u32 + 1} else {0..1};
+        //The union bounds are disjoint, so this draws each pixel once
+        let draw_pieces = |render_pass: &mut RenderPass<'_>, indices: Range<u32>, base_vertex: i32| {
+            for bound in bounds.clone() {
+                render_pass.set_scissor_rect(bound.0, bound.1, bound.2, bound.3);
+                render_pass.draw_indexed(indices.clone(), base_vertex, instances.clone());
+            }
+        };
+        match self.mask_pipelines.as_ref().zip(mask).filter(|_| masks) {
+            Some(((mask_pipeline, masked_pipeline), (mask_start, mask_end, mask_base))) => {
+                render_pass.set_pipeline(mask_pipeline);
+                render_pass.set_stencil_reference(1);
+                draw_pieces(render_pass, mask_start..mask_end, mask_base);
+                render_pass.set_pipeline(masked_pipeline);
+                draw_pieces(render_pass, start..end, base_vertex);
+                //Clear the mask for the shapes after
+                render_pass.set_pipeline(mask_pipeline);
+                render_pass.set_stencil_reference(0);
+                draw_pieces(render_pass, mask_start..mask_end, mask_base);
+                render_pass.set_pipeline(&self.render_pipeline);
+            }
+            None => draw_pieces(render_pass, start..end, base_vertex)
+        }
+        if transform.is_some() {
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[(camera as u64 * self.camera_stride) as u32]);
+        }
+    }
+
+    /// Scissor draws that ignore shape bounds to the clip scissor of
+    /// `set_aa_clip`, or else the whole target, replacing any scissor left in
+    /// the pass. Returns false if the clip scissor is empty.
+    fn set_base_scissor(&self, render_pass: &mut RenderPass<'_>) -> bool {
+        let full = self.target_size.map(|(width, height)| (0, 0, width, height));
+        match self.clip_scissor.or(full) {
+            Some(bound) if bound.2 == 0 || bound.3 == 0 => false,
+            Some(bound) => {
+                render_pass.set_scissor_rect(bound.0, bound.1, bound.2, bound.3);
+                true
+            }
+            None => true
+        }
+    }
+
+    /// Leave the pass with a neutral scissor for whatever is drawn next.
+    fn reset_scissor(&self, render_pass: &mut RenderPass<'_>) {
+        if let Some((width, height)) = self.target_size {
+            render_pass.set_scissor_rect(0, 0, width, height);
+        }
+    }
+
+    /// Round each edge of a normalized `[x, y, width, height]` to the nearest
+    /// pixel, so adjacent rects share edges without gaps or overlap. `None`
+    /// before the target size is known.
+    fn pixel_bound(&self, [x, y, width, height]: [f32; 4]) -> Option<Bound> {
+        let (target_width, target_height) = self.target_size?;
+        let edge = |fraction: f32, size: u32| (fraction * size as f32).round().clamp(0.0, size as f32) as u32;
+        let (left, top) = (edge(x, target_width), edge(y, target_height));
+        let (right, bottom) = (edge(x + width, target_width), edge(y + height, target_height));
+        Some((left, top, right.saturating_sub(left), bottom.saturating_sub(top)))
+    }
+
+    fn bind_pipeline(&self, render_pass: &mut RenderPass<'_>, pipeline: &RenderPipeline, camera_offset: u32) {
+        render_pass.set_pipeline(pipeline);
+        //The caller binds every group of its own layout
+        if self.custom_layout {return;}
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[camera_offset]);
+        for (index, bind_group) in &self.empty_bind_groups {
+            render_pass.set_bind_group(*index, bind_group, &[]);
+        }
+        for (index, bind_group) in &self.user_bind_groups {
+            render_pass.set_bind_group(*index, bind_group, &[]);
+        }
+    }
+
+    /// Draw one triangle into a throwaway 1x1 target so backends that build
+    /// pipelines lazily do it now instead of stalling the first frame. Backends
+    /// that compile eagerly just do a tiny extra draw. Any user bind groups must
+    /// already be set. Does nothing with a layout from `new_with_layout`, whose
+    /// bind groups the renderer does not have.
+    pub fn warmup(&self, device: &Device, queue: &Queue) {
+        if self.custom_layout {return;}
+        let texture = |format| device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d{width: 1, height: 1, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: self.config.multisample.count,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        }).create_view(&TextureViewDescriptor::default());
+        let color_view = texture(self.config.texture_format);
+        let depth_view = self.config.depth_stencil.as_ref().map(|depth_stencil| (texture(depth_stencil.format), depth_stencil.format));
+
+        let vertices = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]].map(|position| Vertex::new(position, [0.0; 3], 0.0));
+        let (vertex_buffer, _) = Self::create_oversized_buffer(
+            device, None, Vertex::gpu_bytes(self.config.gpu_vertex(), &vertices, &mut Vec::new()), BufferUsages::VERTEX
+        );
+        let (index_buffer, _) = Self::create_oversized_buffer(
+            device, None, bytemuck::cast_slice(&[0u16, 1, 2]), BufferUsages::INDEX
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &color_view,
+                    resolve_target: None,
+                    ops: Operations{load: LoadOp::Clear(Color::TRANSPARENT), store: StoreOp::Discard},
+                })],
+                depth_stencil_attachment: depth_view.as_ref().map(|(view, format)| RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: format.has_depth_aspect()
+                        .then_some(Operations{load: LoadOp::Clear(self.clear_depth()), store: StoreOp::Discard}),
+                    stencil_ops: format.has_stencil_aspect()
+                        .then_some(Operations{load: LoadOp::Clear(0), store: StoreOp::Discard}),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.bind_pipeline(&mut render_pass, &self.render_pipeline, 0);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
+            render_pass.draw_indexed(0..3, 0, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Begin a render pass on the encoder with the given attachments and render into it.
+    pub fn encode(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_attachment: RenderPassColorAttachment<'_>,
+        depth_stencil_attachment: Option<RenderPassDepthStencilAttachment<'_>>
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.render(&mut render_pass);
+    }
+
+    /// Like `encode`, clearing the depth attachment to `clear_depth` first so no
+    /// depth from a previous frame leaks into this one. Use `1.0` with the usual
+    /// `Less`/`LessEqual` compare and `0.0` for reverse-z, as returned by
+    /// `LyonRenderer::clear_depth`. A stencil aspect is cleared to zero.
+    pub fn encode_with_depth(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_attachment: RenderPassColorAttachment<'_>,
+        depth_view: &TextureView,
+        clear_depth: f32
+    ) {
+        let has_stencil = self.config.depth_stencil.as_ref()
+            .is_some_and(|depth_stencil| depth_stencil.format.has_stencil_aspect());
+        self.encode(encoder, color_attachment, Some(RenderPassDepthStencilAttachment {
+            view: depth_view,
+            depth_ops: Some(Operations{load: LoadOp::Clear(clear_depth), store: StoreOp::Store}),
+            stencil_ops: has_stencil.then_some(Operations{load: LoadOp::Clear(0), store: StoreOp::Store}),
+        }));
+    }
+
+    /// Render into a single layer of a 2D array texture. The pipeline is not
+    /// multiview, so each layer is targeted through its own 2D view.
+    pub fn encode_to_layer(
+        &self,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        layer: u32,
+        ops: Operations<Color>
+    ) {
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        self.encode(encoder, RenderPassColorAttachment {
+            view: &view,
+            resolve_target: None,
+            ops,
+        }, None);
+    }
+
+    fn rebuild_pipeline(&mut self, device: &Device) {
+        //New modules for the new constants, see `create_shader`
+        self.shader = Self::create_shader(device, self.shader_source.as_ref());
+        self.render_pipeline = self.config.create_render_pipeline(device, &self.shader, &self.pipeline_layout);
+        if self.grid_pipeline.is_some() {
+            self.grid_pipeline = Some(self.config.create_grid_pipeline(device, &self.shader, &self.pipeline_layout));
+        }
+        if self.picking_pipeline.is_some() {
+            self.picking_pipeline = Some(self.config.create_picking_pipeline(device, &self.shader, &self.pipeline_layout));
+        }
+        if let Some((shader, pipeline)) = &mut self.tinted {
+            *shader = Self::create_shader(device, None);
+            *pipeline = self.config.create_tinted_pipeline(device, shader, &self.pipeline_layout);
+        }
+        if self.transparent_pipeline.is_some() {
+            self.transparent_pipeline = Some(self.config.create_transparent_pipeline(device, &self.shader, &self.pipeline_layout));
+        }
+        if self.depth_only_pipeline.is_some() {
+            self.depth_only_pipeline = Some(self.config.create_depth_only_pipeline(device, &self.shader, &self.pipeline_layout));
+        }
+        if self.mask_pipelines.is_some() {
+            self.mask_pipelines = Some((
+                self.config.create_mask_pipeline(device, &self.shader, &self.pipeline_layout),
+                self.config.create_masked_pipeline(device, &self.shader, &self.pipeline_layout)
+            ));
+        }
+        if self.debug_pipeline.is_some() {
+            self.debug_pipeline = Some(self.config.create_debug_pipeline(device, &self.shader, &self.pipeline_layout));
+        }
+        if let Some((shader, pipeline)) = &mut self.stereo {
+            *shader = Self::create_stereo_shader(device);
+            *pipeline = self.config.create_stereo_pipeline(device, shader, &self.pipeline_layout);
+        }
+    }
+
+    fn camera_bytes(cameras: &[[[f32; 4]; 4]], stride: u64) -> Vec<u8> {
+        let mut contents = vec![0u8; cameras.len() * stride as usize];
+        for (camera, slot) in cameras.iter().zip(contents.chunks_mut(stride as usize)) {
+            slot[..Self::CAMERA_SIZE as usize].copy_from_slice(bytemuck::cast_slice(camera));
+        }
+        contents
+    }
+
+    /// The clip, grid, stereo and shape color uniforms, bound after the camera.
+    fn uniform_buffers(&self) -> [&Buffer; 4] {
+        [&self.clip_buffer, &self.grid_buffer, &self.stereo_buffer, &self.shape_color_buffer]
+    }
+
+    fn create_camera_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        buffer: &Buffer,
+        uniforms: [&Buffer; 4]
+    ) -> BindGroup {
+        let camera = BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer,
+                offset: 0,
+                size: BufferSize::new(Self::CAMERA_SIZE),
+            }),
+        };
+        let uniforms = (1..).zip(uniforms).map(|(binding, buffer)| BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        });
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &std::iter::once(camera).chain(uniforms).collect::<Vec<_>>(),
+        })
+    }
+
+    fn write_buffer(queue: &Queue, buffer: &Buffer, slice: &[u8]) {
+        let pad: usize = slice.len() % 4;
+        let slice = if pad != 0 {
+            &[slice, &vec![0u8; pad]].concat()
+        } else {slice};
+        queue.write_buffer(buffer, 0, slice);
+    }
+
+    /// The size of the buffer that would be created for `contents`, or `None`
+    /// if they fit in the current buffer.
+    fn allocation_size<T: bytemuck::Pod>(current_size: u64, contents: &[T]) -> Option<u64> {
+        let size = std::mem::size_of_val(contents) as u64;
+        (size > current_size).then(|| Self::next_copy_buffer_size(size))
+    }
+
+    fn next_copy_buffer_size(size: u64) -> u64 {
+        let align_mask = COPY_BUFFER_ALIGNMENT - 1;
+        ((size.next_power_of_two() + align_mask) & !align_mask).max(COPY_BUFFER_ALIGNMENT)
+    }
+
+    fn create_oversized_buffer(
+        device: &Device,
+        label: Option<&str>,
+        contents: &[u8],
+        usage: BufferUsages,
+    ) -> (Buffer, u64) {
+        let size = Self::next_copy_buffer_size(contents.len() as u64);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label,
+            size,
+            usage,
+            mapped_at_creation: true,
+        });
+        buffer.slice(..).get_mapped_range_mut()[..contents.len()].copy_from_slice(contents);
+        buffer.unmap();
+        (buffer, size)
+    }
+}
@@ -1,25 +1,250 @@
+@group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
+//Min and max corners in target pixels
+@group(0) @binding(1) var<uniform> clip_rect: vec4<f32>;
+
+struct Grid {
+    color: vec4<f32>,
+    major_color: vec4<f32>,
+    spacing: f32,
+    //In pixels
+    thickness: f32,
+    //Zero for no major lines
+    major_every: f32,
+};
+
+@group(0) @binding(2) var<uniform> grid: Grid;
+
 struct VertexInput {
     @location(0) position: vec2<f32>,
     @location(1) color: vec3<f32>,
     @location(2) z: f32,
+    @location(3) normal: vec2<f32>,
+    @location(4) alpha: f32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) normal: vec2<f32>,
+    @location(2) @interpolate(flat) flat_color: vec3<f32>,
+    @location(3) alpha: f32,
 };
 
+override decode_srgb: bool = false;
+override encode_srgb: bool = false;
+override flat_shading: bool = false;
+override aa_clip: bool = false;
+//The width in pixels that aa_clip fades over
+override clip_feather: f32 = 1.0;
+//Set for tinting, which blends the premultiplied color by the blend constant
+override premultiply_alpha: bool = false;
+override reverse_z: bool = false;
+//Set to write the vertex alpha, holding a material id, as is
+override material_ids: bool = false;
+
+//Reverse-z keeps lower z nearer, going from depth 1 at z = 0 to 0 at z = 1
+fn depth_mapped(position: vec4<f32>) -> vec4<f32> {
+    if reverse_z {
+        return vec4<f32>(position.xy, position.w - position.z, position.w);
+    }
+    return position;
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return select(pow((color + 0.055) / 1.055, vec3<f32>(2.4)), color / 12.92, color <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055, color * 12.92, color <= vec3<f32>(0.0031308));
+}
+
+//Flat interpolation takes the first vertex of each triangle
+fn shaded_color(in: VertexOutput) -> vec3<f32> {
+    return select(in.color, in.flat_color, flat_shading);
+}
+
+//Fades out over clip_feather pixels straddling each edge of the clip rect. The
+//feather is clamped to the rect's size so its middle stays fully covered, and
+//kept above zero for smoothstep
+fn clip_coverage(position: vec2<f32>) -> f32 {
+    if !aa_clip {
+        return 1.0;
+    }
+    let feather = clamp(vec2<f32>(clip_feather), vec2<f32>(0.001), clip_rect.zw - clip_rect.xy);
+    let distance = min(position - clip_rect.xy, clip_rect.zw - position);
+    let coverage = smoothstep(-0.5 * feather, 0.5 * feather, distance);
+    return coverage.x * coverage.y;
+}
+
+fn output_color(color: vec3<f32>, alpha: f32, position: vec2<f32>) -> vec4<f32> {
+    let coverage = alpha * clip_coverage(position);
+    var output = color;
+    if encode_srgb {
+        output = linear_to_srgb(color);
+    }
+    if premultiply_alpha {
+        output *= coverage;
+    }
+    if material_ids {
+        return vec4<f32>(output, alpha);
+    }
+    return vec4<f32>(output, coverage);
+}
+
+fn vertex_output(model: VertexInput, view: mat4x4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = model.color;
+    if decode_srgb {
+        out.color = srgb_to_linear(model.color);
+    }
+    out.flat_color = out.color;
+    out.normal = model.normal;
+    out.alpha = model.alpha;
+    out.clip_position = depth_mapped(view * vec4<f32>(model.position, model.z, 1.0));
+    return out;
+}
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
-    var out: VertexOutput;
-    out.color = model.color;
-    out.clip_position = vec4<f32>(model.position, model.z, 1.0);
-    return out;
+    return vertex_output(model, camera);
+}
+
+//RGB color and z of each shape for VertexLayout::Uniform, 16KiB to stay within
+//the smallest uniform binding size
+@group(0) @binding(4) var<uniform> shape_colors: array<vec4<f32>, 1024>;
+
+//Uniform vertices only hold a position and alpha, with a normal for lighting
+struct UniformVertexInput {
+    @location(0) position: vec2<f32>,
+    @location(4) alpha: f32,
+};
+
+struct UniformLitVertexInput {
+    @location(0) position: vec2<f32>,
+    @location(3) normal: vec2<f32>,
+    @location(4) alpha: f32,
+};
+
+fn uniform_vertex(position: vec2<f32>, normal: vec2<f32>, alpha: f32, shape: u32) -> VertexInput {
+    var vertex: VertexInput;
+    vertex.position = position;
+    vertex.color = shape_colors[shape].rgb;
+    vertex.z = shape_colors[shape].w;
+    vertex.normal = normal;
+    vertex.alpha = alpha;
+    return vertex;
+}
+
+//Each shape is drawn as its own instance
+@vertex
+fn vs_uniform(model: UniformVertexInput, @builtin(instance_index) shape: u32) -> VertexOutput {
+    return vertex_output(uniform_vertex(model.position, vec2<f32>(0.0), model.alpha, shape), camera);
+}
+
+@vertex
+fn vs_uniform_lit(model: UniformLitVertexInput, @builtin(instance_index) shape: u32) -> VertexOutput {
+    return vertex_output(uniform_vertex(model.position, model.normal, model.alpha, shape), camera);
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    return output_color(shaded_color(in), in.alpha, in.clip_position.xy);
+}
+
+override light_x: f32 = 0.0;
+override light_y: f32 = 0.0;
+override light_z: f32 = 1.0;
+override ambient: f32 = 0.0;
+
+@fragment
+fn fs_lit(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(vec3<f32>(in.normal, 1.0));
+    let light = normalize(vec3<f32>(light_x, light_y, light_z));
+    let diffuse = max(dot(normal, light), 0.0);
+    return output_color(shaded_color(in) * min(ambient + diffuse, 1.0), in.alpha, in.clip_position.xy);
+}
+
+@fragment
+fn fs_coverage(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.alpha * clip_coverage(in.clip_position.xy));
+}
+
+struct PickingOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) shape: u32,
+};
+
+//Each shape is drawn as its own instance
+@vertex
+fn vs_picking(model: VertexInput, @builtin(instance_index) shape: u32) -> PickingOutput {
+    var out: PickingOutput;
+    out.clip_position = depth_mapped(camera * vec4<f32>(model.position, model.z, 1.0));
+    out.shape = shape;
+    return out;
+}
+
+@vertex
+fn vs_picking_uniform(@location(0) position: vec2<f32>, @builtin(instance_index) shape: u32) -> PickingOutput {
+    var out: PickingOutput;
+    out.clip_position = depth_mapped(camera * vec4<f32>(position, shape_colors[shape].w, 1.0));
+    out.shape = shape;
+    return out;
+}
+
+//Zero is left for the background
+@fragment
+fn fs_picking(in: PickingOutput) -> @location(0) u32 {
+    return in.shape + 1u;
+}
+
+//Neighbouring indices land far apart after the hash
+@fragment
+fn fs_shape_index(in: PickingOutput) -> @location(0) vec4<f32> {
+    var hash = in.shape * 747796405u + 2891336453u;
+    hash = ((hash >> ((hash >> 28u) + 4u)) ^ hash) * 277803737u;
+    hash = (hash >> 22u) ^ hash;
+    let color = vec3<f32>(vec3<u32>(hash, hash >> 8u, hash >> 16u) & vec3<u32>(255u)) / 255.0;
+    return vec4<f32>(color, 1.0);
+}
+
+struct GridOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world: vec2<f32>,
+};
+
+//One triangle covering the target, unprojected onto the z = 0 plane through
+//the affine part of the camera
+@vertex
+fn vs_grid(@builtin(vertex_index) index: u32) -> GridOutput {
+    let position = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    let x = camera[0].xy;
+    let y = camera[1].xy;
+    let inverse = mat2x2<f32>(vec2<f32>(y.y, -x.y), vec2<f32>(-y.x, x.x)) * (1.0 / (x.x * y.y - y.x * x.y));
+    var out: GridOutput;
+    out.clip_position = vec4<f32>(position, 0.0, 1.0);
+    out.world = inverse * (position - camera[3].xy);
+    return out;
+}
+
+//Coverage of the nearest line, with cells one unit apart in `coord`
+fn line_coverage(coord: vec2<f32>, thickness: f32) -> f32 {
+    let pixels = abs(fract(coord - 0.5) - 0.5) / fwidth(coord);
+    let coverage = clamp(thickness * 0.5 + 0.5 - pixels, vec2<f32>(0.0), vec2<f32>(1.0));
+    return max(coverage.x, coverage.y);
+}
+
+@fragment
+fn fs_grid(in: GridOutput) -> @location(0) vec4<f32> {
+    let cell = in.world / grid.spacing;
+    let minor = line_coverage(cell, grid.thickness);
+    //Derivatives need uniform control flow, so major lines are always computed
+    let major = select(0.0, line_coverage(cell / max(grid.major_every, 1.0), grid.thickness), grid.major_every > 0.0);
+    let color = select(grid.color, grid.major_color, major > 0.0 && major >= minor);
+    var rgb = color.rgb;
+    if decode_srgb {
+        rgb = srgb_to_linear(rgb);
+    }
+    return output_color(rgb, color.a * max(minor, major), in.clip_position.xy);
 }
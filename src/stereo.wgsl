@@ -0,0 +1,29 @@
+//Appended to shader.wgsl for the multiview pipeline only, as view_index needs
+//the MULTIVIEW feature for the whole module to validate
+@group(0) @binding(3) var<uniform> eye_cameras: array<mat4x4<f32>, 2>;
+
+@vertex
+fn vs_stereo(
+    model: VertexInput,
+    @builtin(view_index) view: i32,
+) -> VertexOutput {
+    return vertex_output(model, eye_cameras[view]);
+}
+
+@vertex
+fn vs_stereo_uniform(
+    model: UniformVertexInput,
+    @builtin(view_index) view: i32,
+    @builtin(instance_index) shape: u32,
+) -> VertexOutput {
+    return vertex_output(uniform_vertex(model.position, vec2<f32>(0.0), model.alpha, shape), eye_cameras[view]);
+}
+
+@vertex
+fn vs_stereo_uniform_lit(
+    model: UniformLitVertexInput,
+    @builtin(view_index) view: i32,
+    @builtin(instance_index) shape: u32,
+) -> VertexOutput {
+    return vertex_output(uniform_vertex(model.position, model.normal, model.alpha, shape), eye_cameras[view]);
+}
@@ -0,0 +1,369 @@
+use wgpu::{TextureDescriptor, TextureDimension, TextureUsages, TextureFormat, TextureViewDescriptor, Extent3d, CommandEncoderDescriptor, RenderPassColorAttachment, Operations, LoadOp, StoreOp, Color, BufferDescriptor, BufferUsages, ImageCopyBuffer, ImageDataLayout, MapMode, Maintain, COPY_BYTES_PER_ROW_ALIGNMENT, Device, Queue};
+
+use std::path::Path;
+
+use crate::LyonRenderer;
+
+/// Render the prepared shapes into a `size` target cleared to transparent and
+/// compare it with the PNG at `golden_path`, panicking if any channel of any
+/// pixel differs by more than `tolerance`, e.g. a few steps to allow for the
+/// anti-aliasing of different platforms. The renderer should have been resized
+/// to `size` and its target format must have 8 bit RGBA or BGRA channels.
+///
+/// On a mismatch the differing pixels are drawn red over a faded copy of the
+/// golden image, written next to it with a `diff.png` extension. A missing
+/// golden image is written from the render, and the call still panics so a
+/// new golden image is never passed silently.
+pub fn assert_renders_match(
+    renderer: &LyonRenderer,
+    device: &Device,
+    queue: &Queue,
+    size: (u32, u32),
+    golden_path: impl AsRef<Path>,
+    tolerance: u8
+) {
+    let golden_path = golden_path.as_ref();
+    let actual = render_rgba(renderer, device, queue, size);
+    let golden = match std::fs::read(golden_path) {
+        Ok(bytes) => decode_png(&bytes).unwrap_or_else(|error| panic!("Invalid golden image {}: {}", golden_path.display(), error)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            std::fs::write(golden_path, encode_png(size.0, size.1, &actual)).expect("Failed to write the golden image");
+            panic!("No golden image at {}, wrote the render there", golden_path.display());
+        }
+        Err(error) => panic!("Failed to read the golden image {}: {}", golden_path.display(), error)
+    };
+    assert_eq!((golden.0, golden.1), size, "The golden image {} has a different size", golden_path.display());
+
+    let mut differing = 0;
+    let diff = golden.2.chunks_exact(4).zip(actual.chunks_exact(4)).flat_map(|(expected, actual)| {
+        if expected.iter().zip(actual).any(|(expected, actual)| expected.abs_diff(*actual) > tolerance) {
+            differing += 1;
+            [255, 0, 0, 255]
+        } else {
+            let gray = ((expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 6) as u8;
+            [gray, gray, gray, 255]
+        }
+    }).collect::<Vec<u8>>();
+    if differing > 0 {
+        let diff_path = golden_path.with_extension("diff.png");
+        std::fs::write(&diff_path, encode_png(size.0, size.1, &diff)).expect("Failed to write the diff image");
+        panic!(
+            "{} of {} pixels differ from {} by more than {}, see {}",
+            differing, size.0 * size.1, golden_path.display(), tolerance, diff_path.display()
+        );
+    }
+}
+
+/// Render into a new texture and read it back as tightly packed RGBA rows.
+fn render_rgba(renderer: &LyonRenderer, device: &Device, queue: &Queue, (width, height): (u32, u32)) -> Vec<u8> {
+    let requirements = renderer.pass_requirements();
+    let bgra = match requirements.color_format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        format => panic!("Cannot compare renders in {:?}, which is not 8 bit RGBA or BGRA", format)
+    };
+    let texture = |format, sample_count, usage| device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d{width, height, depth_or_array_layers: 1},
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    });
+    let target = texture(requirements.color_format, 1, TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC);
+    let target_view = target.create_view(&TextureViewDescriptor::default());
+    let multisampled = (requirements.sample_count > 1).then(|| {
+        texture(requirements.color_format, requirements.sample_count, TextureUsages::RENDER_ATTACHMENT)
+            .create_view(&TextureViewDescriptor::default())
+    });
+    let color_attachment = RenderPassColorAttachment {
+        view: multisampled.as_ref().unwrap_or(&target_view),
+        resolve_target: multisampled.is_some().then_some(&target_view),
+        ops: Operations{load: LoadOp::Clear(Color::TRANSPARENT), store: StoreOp::Store},
+    };
+
+    //Rows of the copy must be padded to the alignment
+    let row = width * 4;
+    let padded_row = row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: (padded_row * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    match requirements.depth_format {
+        Some(format) => {
+            let depth_view = texture(format, requirements.sample_count, TextureUsages::RENDER_ATTACHMENT)
+                .create_view(&TextureViewDescriptor::default());
+            renderer.encode_with_depth(&mut encoder, color_attachment, &depth_view, renderer.clear_depth());
+        }
+        None => renderer.encode(&mut encoder, color_attachment, None)
+    }
+    encoder.copy_texture_to_buffer(
+        target.as_image_copy(),
+        ImageCopyBuffer{buffer: &buffer, layout: ImageDataLayout{offset: 0, bytes_per_row: Some(padded_row), rows_per_image: None}},
+        target.size()
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |result| result.expect("Failed to map the readback buffer"));
+    device.poll(Maintain::Wait);
+    let mut pixels = Vec::with_capacity((row * height) as usize);
+    for padded in slice.get_mapped_range().chunks_exact(padded_row as usize) {
+        pixels.extend_from_slice(&padded[..row as usize]);
+    }
+    if bgra {
+        pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+    }
+    pixels
+}
+
+/// An 8 bit RGBA PNG with uncompressed deflate blocks.
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks_exact(width as usize * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);}
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in &raw {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    zlib.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = [0; 13];
+    header[..4].copy_from_slice(&width.to_be_bytes());
+    header[4..8].copy_from_slice(&height.to_be_bytes());
+    header[8] = 8;
+    header[9] = 6;
+    for (kind, data) in [(b"IHDR", &header[..]), (b"IDAT", &zlib), (b"IEND", &[])] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    png
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| if crc & 1 == 1 {(crc >> 1) ^ 0xedb88320} else {crc >> 1})
+    })
+}
+
+/// The width, height and RGBA pixels of a non-interlaced 8 bit grayscale, RGB
+/// or RGBA PNG, with or without alpha.
+fn decode_png(png: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let mut rest = png.strip_prefix(b"\x89PNG\r\n\x1a\n").ok_or("Not a PNG file")?;
+    let mut header = None;
+    let mut zlib = Vec::new();
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let data = rest.get(8..8 + len).ok_or("Truncated chunk")?;
+        match &rest[4..8] {
+            b"IHDR" if len == 13 => header = Some(data),
+            b"IDAT" => zlib.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + len..).ok_or("Truncated chunk")?;
+    }
+    let header = header.ok_or("Missing IHDR chunk")?;
+    let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let channels = match (header[8], header[9], header[12]) {
+        (8, 0, 0) => 1,
+        (8, 4, 0) => 2,
+        (8, 2, 0) => 3,
+        (8, 6, 0) => 4,
+        (depth, color, interlace) => return Err(format!(
+            "Unsupported bit depth {}, color type {} or interlace method {}", depth, color, interlace
+        ))
+    };
+
+    let raw = inflate(zlib.get(2..).ok_or("Missing zlib header")?)?;
+    let row = width as usize * channels;
+    if raw.len() < (row + 1) * height as usize {return Err("Not enough image data".to_string());}
+    let mut pixels = vec![0u8; row * height as usize];
+    for y in 0..height as usize {
+        let filter = raw[y * (row + 1)];
+        let line = &raw[y * (row + 1) + 1..(y + 1) * (row + 1)];
+        let (previous, current) = pixels.split_at_mut(y * row);
+        let above = previous.get(previous.len().saturating_sub(row)..).filter(|_| y > 0);
+        let current = &mut current[..row];
+        for x in 0..row {
+            let a = if x >= channels {current[x - channels]} else {0};
+            let b = above.map_or(0, |above| above[x]);
+            let c = if x >= channels {above.map_or(0, |above| above[x - channels])} else {0};
+            current[x] = line[x].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => {
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+                    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+                }
+                filter => return Err(format!("Unknown filter type {}", filter))
+            });
+        }
+    }
+    let rgba = pixels.chunks_exact(channels).flat_map(|pixel| match *pixel {
+        [gray] => [gray, gray, gray, 255],
+        [gray, alpha] => [gray, gray, gray, alpha],
+        [r, g, b] => [r, g, b, 255],
+        [r, g, b, a] => [r, g, b, a],
+        _ => unreachable!()
+    }).collect();
+    Ok((width, height, rgba))
+}
+
+/// Reads deflate bits least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl Bits<'_> {
+    fn read(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.position / 8).ok_or("Truncated deflate stream")?;
+            value |= ((*byte as u32 >> (self.position % 8)) & 1) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// A canonical Huffman code as the number of codes of each length and the
+/// symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        lengths.iter().for_each(|length| counts[*length as usize] += 1);
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate().filter(|(_, length)| **length != 0) {
+            symbols[offsets[*length as usize] as usize] = symbol as u16;
+            offsets[*length as usize] += 1;
+        }
+        Huffman{counts, symbols}
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= bits.read(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied().ok_or("Invalid Huffman code".to_string());
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code".to_string())
+    }
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+    const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+    const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+    const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+    const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+    let mut bits = Bits{data, position: 0};
+    let mut out = Vec::new();
+    loop {
+        let last = bits.read(1)? == 1;
+        let (literals, distances) = match bits.read(2)? {
+            0 => {
+                let start = bits.position.div_ceil(8);
+                let header = data.get(start..start + 4).ok_or("Truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                out.extend_from_slice(data.get(start + 4..start + 4 + len).ok_or("Truncated stored block")?);
+                bits.position = (start + 4 + len) * 8;
+                if last {return Ok(out);}
+                continue;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+            }
+            2 => {
+                let literal_count = bits.read(5)? as usize + 257;
+                let distance_count = bits.read(5)? as usize + 1;
+                let code_length_count = bits.read(4)? as usize + 4;
+                let mut code_lengths = [0u8; 19];
+                for i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+                    code_lengths[*i] = bits.read(3)? as u8;
+                }
+                let code_lengths = Huffman::new(&code_lengths);
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (length, repeat) = match code_lengths.decode(&mut bits)? {
+                        16 => (*lengths.last().ok_or("Repeated code length with no previous length")?, 3 + bits.read(2)?),
+                        17 => (0, 3 + bits.read(3)?),
+                        18 => (0, 11 + bits.read(7)?),
+                        length => (length as u8, 1)
+                    };
+                    lengths.resize(lengths.len() + repeat as usize, length);
+                }
+                if lengths.len() > literal_count + distance_count {return Err("Too many code lengths".to_string());}
+                (Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..]))
+            }
+            _ => return Err("Invalid deflate block type".to_string())
+        };
+        loop {
+            let symbol = literals.decode(&mut bits)? as usize;
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let i = symbol - 257;
+                    let length = *LENGTH_BASE.get(i).ok_or("Invalid length code")? as usize + bits.read(LENGTH_EXTRA[i] as u32)? as usize;
+                    let i = distances.decode(&mut bits)? as usize;
+                    let distance = *DISTANCE_BASE.get(i).ok_or("Invalid distance code")? as usize + bits.read(DISTANCE_EXTRA[i] as u32)? as usize;
+                    let start = out.len().checked_sub(distance).ok_or("Distance before the start of the stream")?;
+                    for i in start..start + length {
+                        out.push(out[i]);
+                    }
+                }
+            }
+        }
+        if last {return Ok(out);}
+    }
+}